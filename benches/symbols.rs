@@ -0,0 +1,66 @@
+use schwift::{symbols::Ident, Interpreter};
+use std::{hint::black_box, time::Instant};
+
+const SIZES: [usize; 4] = [10, 100, 1_000, 10_000];
+const LOOKUPS: u32 = 1_000_000;
+const LOOP: &str = "
+i squanch 0
+while (i less 100000) :<
+    i squanch (i + step)
+>:
+";
+
+fn interpreter_with_globals(count: usize) -> Interpreter {
+    let mut interpreter = Interpreter::new();
+    let mut source = String::new();
+
+    for i in 0..count {
+        source.push_str(&format!("global_{} squanch {}\n", i, i));
+    }
+    source.push_str("step squanch 1\n");
+
+    interpreter.run_str(&source, "<bench>").unwrap();
+    interpreter
+}
+
+fn bench_lookup(count: usize) {
+    let interpreter = interpreter_with_globals(count);
+    let state = interpreter.state();
+    let step = Ident::intern("step");
+
+    let start = Instant::now();
+    for _ in 0..LOOKUPS {
+        black_box(state.get(black_box(step)).unwrap());
+    }
+    let elapsed = start.elapsed();
+
+    println!(
+        "lookup   {:>6} globals: {:>8.2} ns/lookup",
+        count,
+        elapsed.as_nanos() as f64 / f64::from(LOOKUPS)
+    );
+}
+
+fn bench_loop(count: usize) {
+    let mut interpreter = interpreter_with_globals(count);
+
+    let start = Instant::now();
+    interpreter.run_str(LOOP, "<bench>").unwrap();
+    let elapsed = start.elapsed();
+
+    println!(
+        "while    {:>6} globals: {:>8.2} ms",
+        count,
+        elapsed.as_secs_f64() * 1000.0
+    );
+}
+
+fn main() {
+    for &count in &SIZES {
+        bench_lookup(count);
+    }
+
+    for &count in &SIZES {
+        bench_loop(count);
+    }
+}
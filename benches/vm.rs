@@ -0,0 +1,89 @@
+use schwift::Interpreter;
+use std::time::{Duration, Instant};
+
+const RUNS: u32 = 5;
+const PROGRAMS: [(&str, &str); 4] = [
+    (
+        "while",
+        "
+i squanch 0
+while (i less 2000000) :<
+    i squanch (i + 1)
+>:
+",
+    ),
+    (
+        "isPrime",
+        "
+isPrime(x) :<
+    i squanch 2
+    while (i less x) :<
+        if ((x % i) == 0) :<
+            return morty
+        >:
+        i squanch (i + 1)
+    >:
+    return rick
+>:
+prime squanch isPrime(1299709)
+",
+    ),
+    (
+        "lists",
+        "
+sum(xs) :<
+    total squanch 0
+    for x in xs :<
+        total squanch (total + x)
+    >:
+    return total
+>:
+xs on a cob
+for i in 0 up to 100000 :<
+    xs assimilate i
+>:
+total squanch sum(xs)
+",
+    ),
+    (
+        "calls",
+        "
+fib(n) :<
+    if (n less 2) :<
+        return n
+    >:
+    return (fib((n - 1)) + fib((n - 2)))
+>:
+f squanch fib(20)
+",
+    ),
+];
+
+fn best_of(source: &str, vm: bool) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let mut interpreter = Interpreter::new();
+            interpreter.use_vm(vm);
+
+            let start = Instant::now();
+            interpreter.run_str(source, "<bench>").unwrap();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    for &(name, source) in &PROGRAMS {
+        let walker = best_of(source, false);
+        let vm = best_of(source, true);
+
+        println!(
+            "{:<8} walker: {:>8.2} ms  vm: {:>8.2} ms  ({:.2}x)",
+            name,
+            walker.as_secs_f64() * 1000.0,
+            vm.as_secs_f64() * 1000.0,
+            walker.as_secs_f64() / vm.as_secs_f64()
+        );
+    }
+}
@@ -49,15 +49,17 @@ while (count less size) :<
 	if (char == "[") :<
 		l squanch 0
 		if (memory[memPointer] == 0) :<
-			count squanch (count + 1)
-			while ((l more 0) or !(input[count] == "]")) :<
+			while rick :<
+				count squanch (count + 1)
 				if (input[count] == "[") :<
 					l squanch (l + 1)
 				>:
 				if (input[count] == "]") :<
+					if (l == 0) :<
+						break
+					>:
 					l squanch (l - 1)
 				>:
-				count squanch (count + 1)
 			>:
 		>:
 	>:
@@ -65,17 +67,18 @@ while (count less size) :<
 	if (char == "]") :<
 		l squanch 0
 		if !(memory[memPointer] == 0) :<
-			count squanch (count - 1)
-			while ((l more 0) or !(input[count] == "[")) :<
+			while rick :<
+				count squanch (count - 1)
 				if (input[count] == "]") :<
 					l squanch (l + 1)
 				>:
 				if (input[count] == "[") :<
+					if (l == 0) :<
+						break
+					>:
 					l squanch (l - 1)
 				>:
-				count squanch (count - 1)
 			>:
-			count squanch (count - 1)
 		>:
 	>:
 
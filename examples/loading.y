@@ -3,5 +3,7 @@ microverse "./link_test/target/release/libfoo.so" :<
 >:
 
 x squanch matrix(4, 10)
+x[2][3] squanch 1
 
 show me what you got x
+show me what you got x[2][3]
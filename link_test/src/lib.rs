@@ -25,5 +25,6 @@ fn matrix_internal(args: &mut Vec<Value>) -> SwResult<Value> {
     Err(ErrorKind::UnexpectedType {
         expected: Type::List,
         actual: args[0].get_type(),
-    })
+    }
+    .into())
 }
@@ -0,0 +1,41 @@
+use super::{number, Builtin};
+use crate::{
+    error::{ErrorKind, SwResult},
+    state::State,
+    value::Value,
+};
+use std::{
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin::new("time", 0, time),
+    Builtin::new("clock", 0, clock),
+    Builtin::new("sleep", 1, sleep),
+];
+
+/// Seconds since the Unix epoch.
+fn time(_: &State, _: Vec<Value>) -> SwResult<Value> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    Ok(Value::Float(now.as_secs_f64()))
+}
+
+/// Seconds since the interpreter started, never going backwards.
+fn clock(state: &State, _: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(state.started().elapsed().as_secs_f64()))
+}
+
+fn sleep(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let seconds = number(&args[0])?;
+
+    match Duration::try_from_secs_f64(seconds) {
+        Ok(duration) => thread::sleep(duration),
+        Err(_) => return Err(ErrorKind::InvalidArgument("sleep".into(), args[0].clone()).into()),
+    }
+
+    Ok(Value::Bool(true))
+}
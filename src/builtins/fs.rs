@@ -0,0 +1,62 @@
+use super::{string, Builtin};
+use crate::{error::SwResult, state::State, value::Value};
+use std::{fs, io::Write, path::Path};
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin::new("read_file", 1, read_file),
+    Builtin::new("read_lines", 1, read_lines),
+    Builtin::new("write_file", 2, write_file),
+    Builtin::new("append_file", 2, append_file),
+    Builtin::new("exists", 1, exists),
+    Builtin::new("list_dir", 1, list_dir),
+    Builtin::new("delete_file", 1, delete_file),
+];
+
+fn read_file(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Str(fs::read_to_string(string(&args[0])?)?))
+}
+
+fn read_lines(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let contents = fs::read_to_string(string(&args[0])?)?;
+
+    Ok(Value::List(
+        contents
+            .lines()
+            .map(|line| Value::Str(line.into()))
+            .collect(),
+    ))
+}
+
+fn write_file(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    fs::write(string(&args[0])?, string(&args[1])?)?;
+    Ok(Value::Bool(true))
+}
+
+fn append_file(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(string(&args[0])?)?;
+
+    file.write_all(string(&args[1])?.as_bytes())?;
+    Ok(Value::Bool(true))
+}
+
+fn exists(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Bool(Path::new(string(&args[0])?).exists()))
+}
+
+fn list_dir(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let mut names = Vec::new();
+    for entry in fs::read_dir(string(&args[0])?)? {
+        names.push(entry?.file_name().to_string_lossy().into_owned());
+    }
+    names.sort();
+
+    Ok(Value::new(names))
+}
+
+fn delete_file(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    fs::remove_file(string(&args[0])?)?;
+    Ok(Value::Bool(true))
+}
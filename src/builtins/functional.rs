@@ -0,0 +1,74 @@
+use super::{expected, list, Builtin};
+use crate::{
+    error::SwResult,
+    state::State,
+    value::{Type, Value},
+};
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin::new("map", 2, map),
+    Builtin::new("filter", 2, filter),
+    Builtin::new("reduce", 3, reduce),
+    Builtin::new("each", 2, each),
+    Builtin::new("sort_by", 2, sort_by),
+];
+
+fn call(state: &State, function: &Value, args: Vec<Value>) -> SwResult<Value> {
+    state.call_value(state.anonymous(), function, args)
+}
+
+fn map(state: &State, args: Vec<Value>) -> SwResult<Value> {
+    list(&args[0])?
+        .iter()
+        .map(|item| call(state, &args[1], vec![item.clone()]))
+        .collect::<SwResult<_>>()
+        .map(Value::List)
+}
+
+fn filter(state: &State, args: Vec<Value>) -> SwResult<Value> {
+    let mut kept = Vec::new();
+
+    for item in list(&args[0])? {
+        match call(state, &args[1], vec![item.clone()])? {
+            Value::Bool(true) => kept.push(item.clone()),
+            Value::Bool(false) => {}
+            ref value => return Err(expected(Type::Bool, value).into()),
+        }
+    }
+
+    Ok(Value::List(kept))
+}
+
+fn reduce(state: &State, mut args: Vec<Value>) -> SwResult<Value> {
+    let mut accumulator = args.pop().unwrap();
+
+    for item in list(&args[0])? {
+        accumulator = call(state, &args[1], vec![accumulator, item.clone()])?;
+    }
+
+    Ok(accumulator)
+}
+
+/// Calls the function on every item for its side effects, so it doesn't need
+/// to return anything.
+fn each(state: &State, args: Vec<Value>) -> SwResult<Value> {
+    for item in list(&args[0])? {
+        state.invoke(state.anonymous(), &args[1], vec![item.clone()])?;
+    }
+
+    Ok(Value::Bool(true))
+}
+
+/// Sorts the items by the key the function gives for each of them.
+fn sort_by(state: &State, args: Vec<Value>) -> SwResult<Value> {
+    let mut keyed = list(&args[0])?
+        .iter()
+        .map(|item| Ok((call(state, &args[1], vec![item.clone()])?, item.clone())))
+        .collect::<SwResult<Vec<_>>>()?;
+
+    keyed.sort_by(|(k1, _), (k2, _)| k1.order(k2));
+
+    Ok(Value::List(
+        keyed.into_iter().map(|(_, item)| item).collect(),
+    ))
+}
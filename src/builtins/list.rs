@@ -0,0 +1,55 @@
+use super::Builtin;
+use crate::{
+    error::SwResult,
+    state::State,
+    value::{IntT, Value},
+};
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin::new("sort", 1, sort),
+    Builtin::new("reverse", 1, reverse),
+    Builtin::new("slice", 3, slice),
+    Builtin::new("insert", 3, insert),
+    Builtin::new("concat", 2, concat),
+    Builtin::new("contains", 2, contains),
+    Builtin::new("index_of", 2, index_of),
+    Builtin::new("count", 2, count),
+];
+
+fn sort(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    args[0].sorted()
+}
+
+fn reverse(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    args[0].reversed()
+}
+
+fn slice(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    args[0].slice(&args[1], &args[2])
+}
+
+fn insert(_: &State, mut args: Vec<Value>) -> SwResult<Value> {
+    let value = args.pop().unwrap();
+    let index = args.pop().unwrap();
+    let mut list = args.pop().unwrap();
+
+    list.insert(&index, value)?;
+    Ok(list)
+}
+
+fn concat(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    args[0].concat(&args[1])
+}
+
+fn contains(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    args[0].contains(&args[1]).map(Value::Bool)
+}
+
+fn index_of(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let index = args[0].index_of(&args[1])?;
+    Ok(Value::Int(index.map_or(-1, |i| i as IntT)))
+}
+
+fn count(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Int(args[0].count(&args[1])? as IntT))
+}
@@ -0,0 +1,146 @@
+use super::{expected, int, number, Builtin};
+use crate::{
+    error::SwResult,
+    state::State,
+    value::{FloatT, IntT, Type, Value},
+};
+use std::{cmp::Ordering, convert::TryFrom};
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin::new("abs", 1, abs),
+    Builtin::new("sqrt", 1, sqrt),
+    Builtin::new("pow", 2, pow),
+    Builtin::new("floor", 1, floor),
+    Builtin::new("ceil", 1, ceil),
+    Builtin::new("round", 1, round),
+    Builtin::new("sin", 1, sin),
+    Builtin::new("cos", 1, cos),
+    Builtin::new("tan", 1, tan),
+    Builtin::new("asin", 1, asin),
+    Builtin::new("acos", 1, acos),
+    Builtin::new("atan", 1, atan),
+    Builtin::new("atan2", 2, atan2),
+    Builtin::new("min", 2, min),
+    Builtin::new("max", 2, max),
+    Builtin::new("int", 1, to_int),
+    Builtin::new("float", 1, to_float),
+    Builtin::new("str", 1, to_str),
+];
+
+fn abs(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    match args[0] {
+        Value::Int(i) => Ok(Value::Int(i.wrapping_abs())),
+        ref value => Ok(Value::Float(number(value)?.abs())),
+    }
+}
+
+fn sqrt(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(number(&args[0])?.sqrt()))
+}
+
+fn pow(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    if let (&Value::Int(base), &Value::Int(exp)) = (&args[0], &args[1]) {
+        if let Ok(exp) = u32::try_from(exp) {
+            return Ok(Value::Int(base.wrapping_pow(exp)));
+        }
+    }
+
+    Ok(Value::Float(number(&args[0])?.powf(number(&args[1])?)))
+}
+
+fn rounded(value: &Value, round: fn(FloatT) -> FloatT) -> SwResult<Value> {
+    match *value {
+        Value::Int(i) => Ok(Value::Int(i)),
+        _ => Ok(Value::Int(round(number(value)?) as IntT)),
+    }
+}
+
+fn floor(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    rounded(&args[0], FloatT::floor)
+}
+
+fn ceil(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    rounded(&args[0], FloatT::ceil)
+}
+
+fn round(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    rounded(&args[0], FloatT::round)
+}
+
+fn sin(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(number(&args[0])?.sin()))
+}
+
+fn cos(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(number(&args[0])?.cos()))
+}
+
+fn tan(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(number(&args[0])?.tan()))
+}
+
+fn asin(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(number(&args[0])?.asin()))
+}
+
+fn acos(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(number(&args[0])?.acos()))
+}
+
+fn atan(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(number(&args[0])?.atan()))
+}
+
+fn atan2(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(number(&args[0])?.atan2(number(&args[1])?)))
+}
+
+fn min(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    pick(args, Ordering::Less)
+}
+
+fn max(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    pick(args, Ordering::Greater)
+}
+
+/// The right number if it's ordered `wanted` from the left one, otherwise the
+/// left. Compared with `Value::order`, since casting large ints to floats
+/// rounds them.
+fn pick(mut args: Vec<Value>, wanted: Ordering) -> SwResult<Value> {
+    let (right, left) = (args.pop().unwrap(), args.pop().unwrap());
+    number(&right)?;
+    number(&left)?;
+
+    if right.order(&left) == wanted {
+        Ok(right)
+    } else {
+        Ok(left)
+    }
+}
+
+fn to_int(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    match args[0] {
+        Value::Float(f) => Ok(Value::Int(f as IntT)),
+        Value::Str(ref s) => s
+            .trim()
+            .parse()
+            .map(Value::Int)
+            .map_err(|_| expected(Type::Int, &args[0]).into()),
+        ref value => int(value).map(Value::Int),
+    }
+}
+
+fn to_float(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    match args[0] {
+        Value::Str(ref s) => s
+            .trim()
+            .parse()
+            .map(Value::Float)
+            .map_err(|_| expected(Type::Float, &args[0]).into()),
+        ref value => number(value).map(Value::Float),
+    }
+}
+
+fn to_str(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Str(args[0].to_string()))
+}
@@ -0,0 +1,97 @@
+use crate::{
+    error::{ErrorKind, SwResult},
+    state::State,
+    value::{FloatT, Func, IntT, Type, Value},
+};
+
+mod clock;
+mod fs;
+mod functional;
+mod list;
+mod math;
+mod random;
+mod string;
+
+#[cfg(test)]
+mod test;
+
+pub type BuiltinFn = fn(&State, Vec<Value>) -> SwResult<Value>;
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub arity: usize,
+    f: BuiltinFn,
+}
+
+impl Builtin {
+    const fn new(name: &'static str, arity: usize, f: BuiltinFn) -> Self {
+        Self { name, arity, f }
+    }
+
+    pub(crate) fn call(&self, state: &State, args: Vec<Value>) -> SwResult<Value> {
+        if args.len() != self.arity {
+            return Err(
+                ErrorKind::InvalidArguments(self.name.into(), self.arity, args.len()).into(),
+            );
+        }
+
+        (self.f)(state, args)
+    }
+}
+
+const BUILTINS: &[&[Builtin]] = &[
+    string::BUILTINS,
+    math::BUILTINS,
+    list::BUILTINS,
+    functional::BUILTINS,
+    fs::BUILTINS,
+    random::BUILTINS,
+    clock::BUILTINS,
+];
+
+/// Puts every builtin in the global scope of `state`.
+pub(crate) fn register(state: &mut State) {
+    for builtin in BUILTINS.iter().flat_map(|group| group.iter()) {
+        state.insert(builtin.name, Func::builtin(builtin));
+    }
+}
+
+fn expected(expected: Type, actual: &Value) -> ErrorKind {
+    ErrorKind::UnexpectedType {
+        expected,
+        actual: actual.get_type(),
+    }
+}
+
+fn string(value: &Value) -> SwResult<&str> {
+    match *value {
+        Value::Str(ref s) => Ok(s),
+        _ => Err(expected(Type::Str, value).into()),
+    }
+}
+
+fn int(value: &Value) -> SwResult<IntT> {
+    match *value {
+        Value::Int(i) => Ok(i),
+        _ => Err(expected(Type::Int, value).into()),
+    }
+}
+
+fn number(value: &Value) -> SwResult<FloatT> {
+    match *value {
+        Value::Int(i) => Ok(i as FloatT),
+        Value::Float(f) => Ok(f),
+        _ => Err(expected(
+            Type::Union(Box::new(Type::Int), Box::new(Type::Float)),
+            value,
+        )
+        .into()),
+    }
+}
+
+fn list(value: &Value) -> SwResult<&[Value]> {
+    match *value {
+        Value::List(ref l) => Ok(l),
+        _ => Err(expected(Type::List, value).into()),
+    }
+}
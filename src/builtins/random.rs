@@ -0,0 +1,51 @@
+use super::{int, list, Builtin};
+use crate::{
+    error::{ErrorKind, SwResult},
+    state::State,
+    value::Value,
+};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin::new("random_int", 2, random_int),
+    Builtin::new("random_float", 0, random_float),
+    Builtin::new("shuffle", 1, shuffle),
+    Builtin::new("choice", 1, choice),
+    Builtin::new("seed", 1, seed),
+];
+
+fn random_int(state: &State, args: Vec<Value>) -> SwResult<Value> {
+    let low = int(&args[0])?;
+    let high = int(&args[1])?;
+
+    if high < low {
+        return Err(ErrorKind::EmptyRange(low, high).into());
+    }
+
+    Ok(Value::Int(state.rng().gen_range(low..=high)))
+}
+
+fn random_float(state: &State, _: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Float(state.rng().gen()))
+}
+
+fn shuffle(state: &State, args: Vec<Value>) -> SwResult<Value> {
+    let mut items = list(&args[0])?.to_vec();
+    items.shuffle(&mut *state.rng());
+
+    Ok(Value::List(items))
+}
+
+fn choice(state: &State, args: Vec<Value>) -> SwResult<Value> {
+    let items = list(&args[0])?;
+
+    match items.choose(&mut *state.rng()) {
+        Some(item) => Ok(item.clone()),
+        None => Err(ErrorKind::IndexOutOfBounds { len: 0, index: 0 }.into()),
+    }
+}
+
+fn seed(state: &State, args: Vec<Value>) -> SwResult<Value> {
+    *state.rng() = StdRng::seed_from_u64(int(&args[0])? as u64);
+    Ok(Value::Bool(true))
+}
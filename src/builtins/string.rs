@@ -0,0 +1,130 @@
+use super::{int, list, string, Builtin};
+use crate::{
+    error::{ErrorKind, SwResult},
+    state::State,
+    value::{IntT, Value},
+};
+use std::convert::TryFrom;
+
+pub(super) const BUILTINS: &[Builtin] = &[
+    Builtin::new("chr", 1, chr),
+    Builtin::new("ascii", 1, chr),
+    Builtin::new("ord", 1, ord),
+    Builtin::new("split", 2, split),
+    Builtin::new("join", 2, join),
+    Builtin::new("substring", 3, substring),
+    Builtin::new("find", 2, find),
+    Builtin::new("replace", 3, replace),
+    Builtin::new("upper", 1, upper),
+    Builtin::new("lower", 1, lower),
+    Builtin::new("trim", 1, trim),
+    Builtin::new("starts_with", 2, starts_with),
+    Builtin::new("ends_with", 2, ends_with),
+];
+
+fn chr(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let code = int(&args[0])?;
+
+    u32::try_from(code)
+        .ok()
+        .and_then(char::from_u32)
+        .map(|c| Value::Str(c.to_string()))
+        .ok_or_else(|| ErrorKind::InvalidCodePoint(code).into())
+}
+
+fn ord(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let s = string(&args[0])?;
+    let mut chars = s.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(Value::Int(c as IntT)),
+        _ => Err(ErrorKind::NotACharacter(s.into()).into()),
+    }
+}
+
+fn split(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let s = string(&args[0])?;
+    let separator = string(&args[1])?;
+
+    let parts = if separator.is_empty() {
+        s.chars().map(|c| Value::Str(c.to_string())).collect()
+    } else {
+        s.split(separator)
+            .map(|part| Value::Str(part.into()))
+            .collect()
+    };
+
+    Ok(Value::List(parts))
+}
+
+fn join(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let items = list(&args[0])?;
+    let separator = string(&args[1])?;
+
+    let items: Vec<String> = items.iter().map(Value::to_string).collect();
+    Ok(Value::Str(items.join(separator)))
+}
+
+fn substring(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let s = string(&args[0])?;
+    let start = int(&args[1])?;
+    let end = int(&args[2])?;
+
+    let len = s.chars().count();
+    for &index in &[start, end] {
+        if index < 0 || index as usize > len {
+            return Err(ErrorKind::IndexOutOfBounds {
+                len,
+                index: index as usize,
+            }
+            .into());
+        }
+    }
+
+    let taken = (end - start).max(0) as usize;
+    Ok(Value::Str(
+        s.chars().skip(start as usize).take(taken).collect(),
+    ))
+}
+
+fn find(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let s = string(&args[0])?;
+    let needle = string(&args[1])?;
+
+    let index = match s.find(needle) {
+        Some(byte) => s[..byte].chars().count() as IntT,
+        None => -1,
+    };
+
+    Ok(Value::Int(index))
+}
+
+fn replace(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    let s = string(&args[0])?;
+    let from = string(&args[1])?;
+    let to = string(&args[2])?;
+
+    Ok(Value::Str(s.replace(from, to)))
+}
+
+fn upper(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Str(string(&args[0])?.to_uppercase()))
+}
+
+fn lower(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Str(string(&args[0])?.to_lowercase()))
+}
+
+fn trim(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Str(string(&args[0])?.trim().into()))
+}
+
+fn starts_with(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Bool(
+        string(&args[0])?.starts_with(string(&args[1])?),
+    ))
+}
+
+fn ends_with(_: &State, args: Vec<Value>) -> SwResult<Value> {
+    Ok(Value::Bool(string(&args[0])?.ends_with(string(&args[1])?)))
+}
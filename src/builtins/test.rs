@@ -0,0 +1,481 @@
+use crate::{
+    error::{EitherError, ErrorKind as EKind},
+    grammar,
+    state::State,
+    value::{Type, Value},
+};
+
+fn eval(source: &str) -> Value {
+    let state = State::new();
+    let exp = grammar::expression(source).unwrap();
+    exp.evaluate(&state).unwrap().into_owned()
+}
+
+fn eval_err(source: &str) -> EitherError {
+    let state = State::new();
+    let exp = grammar::expression(source).unwrap();
+    exp.evaluate(&state).unwrap_err()
+}
+
+#[test]
+fn chr_and_ord() {
+    assert_eq!(eval("chr(65)"), Value::new("A"));
+    assert_eq!(eval("ascii(10)"), Value::new("\n"));
+    assert_eq!(eval("ascii(129426)"), Value::new("🦒"));
+    assert_eq!(eval("ord(\"é\")"), Value::new(233));
+    assert_eq!(eval("ord(chr(128125))"), Value::new(128125));
+
+    assert_eq!(
+        *eval_err("chr(55296)").kind(),
+        EKind::InvalidCodePoint(55296)
+    );
+    assert_eq!(
+        *eval_err("ord(\"rick\")").kind(),
+        EKind::NotACharacter("rick".into())
+    );
+}
+
+#[test]
+fn split_and_join() {
+    assert_eq!(
+        eval(r#"split("wubba lubba dub dub", " ")"#),
+        Value::new(vec!["wubba", "lubba", "dub", "dub"])
+    );
+    assert_eq!(eval(r#"split("née", "")"#), Value::new(vec!["n", "é", "e"]));
+    assert_eq!(
+        eval(r#"join(split("a,b,c", ","), "-")"#),
+        Value::new("a-b-c")
+    );
+}
+
+#[test]
+fn string_length_counts_characters() {
+    let mut state = State::new();
+    state.insert("s", "héllo");
+
+    let eval = |source: &str| {
+        let exp = grammar::expression(source).unwrap();
+        exp.evaluate(&state).unwrap().into_owned()
+    };
+
+    assert_eq!(eval("s squanch"), Value::new(5));
+    assert_eq!(eval("substring(s, 0, s squanch)"), Value::new("héllo"));
+}
+
+#[test]
+fn searching() {
+    assert_eq!(eval(r#"find("pickle rick", "rick")"#), Value::new(7));
+    assert_eq!(eval(r#"find("ñope", "o")"#), Value::new(1));
+    assert_eq!(eval(r#"find("morty", "rick")"#), Value::new(-1));
+    assert_eq!(
+        eval(r#"starts_with("pickle rick", "pickle")"#),
+        Value::new(true)
+    );
+    assert_eq!(
+        eval(r#"ends_with("pickle rick", "pickle")"#),
+        Value::new(false)
+    );
+}
+
+#[test]
+fn transforming() {
+    assert_eq!(eval(r#"substring("squanch", 1, 4)"#), Value::new("qua"));
+    assert_eq!(eval(r#"substring("über", 0, 2)"#), Value::new("üb"));
+    assert_eq!(
+        eval(r#"replace("rick and rick", "rick", "morty")"#),
+        Value::new("morty and morty")
+    );
+    assert_eq!(eval(r#"upper("schwifty")"#), Value::new("SCHWIFTY"));
+    assert_eq!(eval(r#"lower("SCHWIFTY")"#), Value::new("schwifty"));
+    assert_eq!(
+        eval(r#"trim("  get schwifty  ")"#),
+        Value::new("get schwifty")
+    );
+
+    assert_eq!(
+        *eval_err(r#"substring("rick", 2, 5)"#).kind(),
+        EKind::IndexOutOfBounds { len: 4, index: 5 }
+    );
+}
+
+#[test]
+fn checks_arguments() {
+    assert_eq!(
+        *eval_err(r#"upper("a", "b")"#).kind(),
+        EKind::InvalidArguments("upper".into(), 1, 2)
+    );
+    assert_eq!(
+        *eval_err("upper(1)").kind(),
+        EKind::UnexpectedType {
+            expected: Type::Str,
+            actual: Type::Int,
+        }
+    );
+}
+
+#[test]
+fn builtins_can_be_shadowed() {
+    let mut state = State::new();
+    let code = grammar::file(
+        r#"
+    upper(s) :<
+        return "shadowed"
+    >:
+    x squanch upper("a")
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("x").unwrap(), Value::new("shadowed"));
+}
+
+#[test]
+fn math() {
+    assert_eq!(eval("abs(-3)"), Value::new(3));
+    assert_eq!(eval("abs((0 - 2.5))"), Value::new(2.5));
+    assert_eq!(eval("sqrt(16)"), Value::new(4.0));
+    assert_eq!(eval("pow(2, 10)"), Value::new(1024));
+    assert_eq!(eval("pow(2, -1)"), Value::new(0.5));
+    assert_eq!(eval("pow(9, 0.5)"), Value::new(3.0));
+    assert_eq!(eval("floor(2.7)"), Value::new(2));
+    assert_eq!(eval("ceil(2.2)"), Value::new(3));
+    assert_eq!(eval("round(2.5)"), Value::new(3));
+    assert_eq!(eval("round(7)"), Value::new(7));
+    assert_eq!(eval("sin(0)"), Value::new(0.0));
+    assert_eq!(eval("cos(0)"), Value::new(1.0));
+    assert_eq!(eval("round((atan2(1, 1) * 4000))"), Value::new(3142));
+    assert_eq!(eval("min(3, 2.5)"), Value::new(2.5));
+    assert_eq!(eval("max(3, 2.5)"), Value::new(3));
+    assert_eq!(
+        eval("min(9007199254740993, 9007199254740992)"),
+        Value::new(9_007_199_254_740_992)
+    );
+    assert_eq!(
+        eval("max(9007199254740992, 9007199254740993)"),
+        Value::new(9_007_199_254_740_993)
+    );
+
+    assert_eq!(
+        *eval_err("sqrt(\"4\")").kind(),
+        EKind::UnexpectedType {
+            expected: Type::Union(Box::new(Type::Int), Box::new(Type::Float)),
+            actual: Type::Str,
+        }
+    );
+}
+
+#[test]
+fn conversions() {
+    assert_eq!(eval("int(\" 42 \")"), Value::new(42));
+    assert_eq!(eval("int((0 - 2.9))"), Value::new(-2));
+    assert_eq!(eval("float(\"2.5\")"), Value::new(2.5));
+    assert_eq!(eval("float(2)"), Value::new(2.0));
+    assert_eq!(eval("str(137)"), Value::new("137"));
+    assert_eq!(eval("str(rick)"), Value::new("rick"));
+    assert_eq!(eval("(str(1) + str(2))"), Value::new("12"));
+
+    assert_eq!(
+        *eval_err("int(\"c-137\")").kind(),
+        EKind::UnexpectedType {
+            expected: Type::Int,
+            actual: Type::Str,
+        }
+    );
+    assert_eq!(
+        *eval_err("float(morty)").kind(),
+        EKind::UnexpectedType {
+            expected: Type::Union(Box::new(Type::Int), Box::new(Type::Float)),
+            actual: Type::Bool,
+        }
+    );
+}
+
+#[test]
+fn files() {
+    let dir = std::env::temp_dir().join(format!("schwift-fs-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = dir.display().to_string();
+
+    let mut state = State::new();
+    state.insert("dir", dir.clone());
+
+    let code = grammar::file(
+        r#"
+    path squanch (dir + "/plumbus.txt")
+    before squanch exists(path)
+    write_file(path, ("fleeb" + chr(10)))
+    append_file(path, ("schleem" + chr(10)))
+    contents squanch read_file(path)
+    lines squanch read_lines(path)
+    files squanch list_dir(dir)
+    delete_file(path)
+    after squanch exists(path)
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    std::fs::remove_dir(&dir).unwrap();
+
+    assert_eq!(*state.get("before").unwrap(), Value::new(false));
+    assert_eq!(
+        *state.get("contents").unwrap(),
+        Value::new("fleeb\nschleem\n")
+    );
+    assert_eq!(
+        *state.get("lines").unwrap(),
+        Value::new(vec!["fleeb", "schleem"])
+    );
+    assert_eq!(
+        *state.get("files").unwrap(),
+        Value::new(vec!["plumbus.txt"])
+    );
+    assert_eq!(*state.get("after").unwrap(), Value::new(false));
+}
+
+#[test]
+fn file_errors_are_catchable() {
+    let mut state = State::new();
+    let code = grammar::file(
+        r#"
+    normal plan :<
+        x squanch read_file("/no/such/plumbus")
+    >: plan for failure e :<
+        kind squanch e["kind"]
+    >:
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("kind").unwrap(), Value::new("IOError"));
+}
+
+#[test]
+fn seeded_randomness_is_reproducible() {
+    let run = || {
+        let mut state = State::new();
+        let code = grammar::file(
+            r#"
+    seed(137)
+    a squanch random_int(1, 100)
+    b squanch random_float()
+    c squanch shuffle(split("abcdefgh", ""))
+    d squanch choice(c)
+    "#,
+        )
+        .unwrap();
+
+        state.run(&code).unwrap();
+        ["a", "b", "c", "d"]
+            .iter()
+            .map(|name| state.get(*name).unwrap().clone())
+            .collect::<Vec<_>>()
+    };
+
+    let first = run();
+    assert_eq!(first, run());
+
+    match first[1] {
+        Value::Float(f) => assert!((0.0..1.0).contains(&f)),
+        ref value => panic!("expected a float, got {:?}", value),
+    }
+
+    let mut letters: Vec<String> = match first[2] {
+        Value::List(ref l) => l.iter().map(Value::to_string).collect(),
+        ref value => panic!("expected a list, got {:?}", value),
+    };
+    letters.sort();
+    assert_eq!(letters.concat(), "abcdefgh");
+}
+
+#[test]
+fn random_ranges() {
+    for _ in 0..20 {
+        match eval("random_int(3, 5)") {
+            Value::Int(i) => assert!((3..=5).contains(&i)),
+            value => panic!("expected an int, got {:?}", value),
+        }
+    }
+
+    assert_eq!(eval("random_int(7, 7)"), Value::new(7));
+    assert_eq!(
+        *eval_err("random_int(5, 3)").kind(),
+        EKind::EmptyRange(5, 3)
+    );
+    assert_eq!(
+        *eval_err(r#"choice(split("", ""))"#).kind(),
+        EKind::IndexOutOfBounds { len: 0, index: 0 }
+    );
+}
+
+#[test]
+fn clocks() {
+    let mut state = State::new();
+    let code = grammar::file(
+        r#"
+    start squanch clock()
+    sleep(0.01)
+    elapsed squanch (clock() - start)
+    now squanch time()
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+
+    match *state.get("elapsed").unwrap() {
+        Value::Float(f) => assert!(f >= 0.01),
+        ref value => panic!("expected a float, got {:?}", value),
+    }
+    match *state.get("now").unwrap() {
+        Value::Float(f) => assert!(f > 1_500_000_000.0),
+        ref value => panic!("expected a float, got {:?}", value),
+    }
+
+    assert_eq!(
+        *eval_err("sleep(-1)").kind(),
+        EKind::InvalidArgument("sleep".into(), Value::new(-1))
+    );
+    assert_eq!(
+        eval_err("sleep(0 - 0.5)").kind().to_string(),
+        "Morty, sleep(-0.5)? That doesn't even make sense!"
+    );
+}
+
+#[test]
+fn sorting_orders_across_types() {
+    let mut m = crate::value::Map::new();
+    m.insert(crate::value::Key::Int(1), Value::new(1));
+
+    let mixed = Value::new(vec![
+        Value::new("b"),
+        Value::Map(m.clone()),
+        Value::new(2.5),
+        Value::new(vec![2]),
+        Value::new(true),
+        Value::new(3),
+        Value::new("a"),
+        Value::new(vec![1, 5]),
+        Value::new(false),
+        Value::new(1),
+    ]);
+
+    assert_eq!(
+        mixed.sorted().unwrap(),
+        Value::new(vec![
+            Value::new(false),
+            Value::new(true),
+            Value::new(1),
+            Value::new(2.5),
+            Value::new(3),
+            Value::new("a"),
+            Value::new("b"),
+            Value::new(vec![1, 5]),
+            Value::new(vec![2]),
+            Value::Map(m),
+        ])
+    );
+}
+
+#[test]
+fn sorting_compares_large_ints_and_floats_exactly() {
+    let big = 1i64 << 53;
+    let numbers = Value::new(vec![
+        Value::new(big + 1),
+        Value::new(big as f64),
+        Value::new(big),
+        Value::new(-0.0),
+        Value::new(big - 1),
+        Value::new(i64::MAX),
+        Value::new(f64::NAN),
+        Value::new(9.3e18),
+        Value::new(0),
+        Value::new(-0.5),
+        Value::new(i64::MIN),
+    ]);
+
+    let sorted = match numbers.sorted().unwrap() {
+        Value::List(l) => l,
+        value => panic!("expected a list, got {:?}", value),
+    };
+
+    assert_eq!(sorted[0], Value::new(i64::MIN));
+    assert_eq!(sorted[1], Value::new(-0.5));
+    assert_eq!(sorted[4], Value::new(big - 1));
+    assert_eq!(sorted[7], Value::new(big + 1));
+    assert_eq!(sorted[8..10], [Value::new(i64::MAX), Value::new(9.3e18)]);
+    assert!(matches!(sorted[10], Value::Float(f) if f.is_nan()));
+
+    for a in &sorted {
+        for b in &sorted {
+            assert_eq!(a.order(b), b.order(a).reverse());
+            for c in &sorted {
+                if a.order(b).is_le() && b.order(c).is_le() {
+                    assert!(a.order(c).is_le(), "{:?} <= {:?} <= {:?}", a, b, c);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn lists() {
+    let mut state = State::new();
+    let code = grammar::file(
+        r#"
+    l on a cob
+    l assimilate 3
+    l assimilate 1
+    l assimilate 2
+    l assimilate 1
+
+    sorted squanch sort(l)
+    reversed squanch reverse(l)
+    middle squanch slice(l, 1, 3)
+    inserted squanch insert(l, 4, 9)
+    joined squanch concat(l, sorted)
+    has_two squanch contains(l, 2)
+    has_five squanch contains(l, 5)
+    where squanch index_of(l, 1)
+    nowhere squanch index_of(l, 5)
+    ones squanch count(l, 1)
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+
+    let get = |name: &str| state.get(name).unwrap().clone();
+    assert_eq!(get("sorted"), Value::new(vec![1, 1, 2, 3]));
+    assert_eq!(get("reversed"), Value::new(vec![1, 2, 1, 3]));
+    assert_eq!(get("middle"), Value::new(vec![1, 2]));
+    assert_eq!(get("inserted"), Value::new(vec![3, 1, 2, 1, 9]));
+    assert_eq!(get("joined"), Value::new(vec![3, 1, 2, 1, 1, 1, 2, 3]));
+    assert_eq!(get("has_two"), Value::new(true));
+    assert_eq!(get("has_five"), Value::new(false));
+    assert_eq!(get("where"), Value::new(1));
+    assert_eq!(get("nowhere"), Value::new(-1));
+    assert_eq!(get("ones"), Value::new(2));
+    assert_eq!(get("l"), Value::new(vec![3, 1, 2, 1]));
+}
+
+#[test]
+fn list_errors() {
+    assert_eq!(
+        *eval_err(r#"slice(split("abc", ""), 1, 4)"#).kind(),
+        EKind::IndexOutOfBounds { len: 3, index: 4 }
+    );
+    assert_eq!(
+        *eval_err(r#"insert(split("abc", ""), 5, 1)"#).kind(),
+        EKind::IndexOutOfBounds { len: 3, index: 5 }
+    );
+    assert_eq!(
+        *eval_err(r#"sort("cba")"#).kind(),
+        EKind::UnexpectedType {
+            expected: Type::List,
+            actual: Type::Str,
+        }
+    );
+}
@@ -1,6 +1,12 @@
-use crate::{grammar, statement::Statement, value, Operator};
+use crate::{
+    grammar,
+    span::{FileId, Files, Span},
+    statement::Statement,
+    value::{self, IntT, Value},
+    Operator,
+};
 use rand::{seq::SliceRandom, thread_rng};
-use std::{fmt::Write, io, process};
+use std::{fmt::Write, io, rc::Rc};
 
 pub type SwResult<T> = Result<T, EitherError>;
 
@@ -21,8 +27,16 @@ pub const QUOTES: [&str; 9] = [
 #[derive(Debug, thiserror::Error, PartialEq)]
 #[error("An error ocurred")]
 pub struct ErrorWithContext {
+    // Boxed so results carrying it stay small on the happy path.
+    context: Box<Context>,
+}
+
+#[derive(Debug, PartialEq)]
+struct Context {
     place: Statement,
 
+    span: Span,
+
     kind: ErrorKind,
 }
 
@@ -32,7 +46,10 @@ pub enum EitherError {
     WithContext(#[from] ErrorWithContext),
 
     #[error("An error with no statment context")]
-    NoContext(#[source] ErrorKind),
+    NoContext(#[source] Box<ErrorKind>),
+
+    #[error("An error in an expression with no statement context")]
+    WithSpan(#[source] Box<ErrorKind>, Span),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -83,13 +100,78 @@ pub enum ErrorKind {
         library: String,
     },
 
-    #[error("That's an older code, Morty and it does not check out. That microverse can only be run by schwift {0}, but this is {}", crate::LIBSCHWIFT_ABI_COMPAT)]
+    #[error("That's an older code, Morty and it does not check out. That microverse can only be run by schwift ABI {0}, but this one takes {}", crate::plugin::ABI_VERSION)]
     IncompatibleAbi(u32),
 
+    #[error("{0} was built for schwift ABI {}, Morty, and I'm not sticking my hand in there to find out what its values look like now. Rebuild it against ABI {}, or pass --legacy-abi if it was built with this exact rustc and schwift.", crate::plugin::LEGACY_ABI_VERSION, crate::plugin::ABI_VERSION)]
+    LegacyAbi(String),
+
     #[error(
         "I told you how a Microverse works Morty. At what point exactly did you stop listening?"
     )]
     DylibReturnedNil,
+
+    #[error("You can't take a {0} into a microverse, Morty! The whole thing would collapse!")]
+    UnportableValue(value::Type),
+
+    #[error("That microverse handed me garbage, Morty: {0}")]
+    CorruptPluginValue(String),
+
+    #[error("Your microverse blew up, Morty: {0}")]
+    PluginError(String),
+
+    #[error("{0} isn't a character in any dimension I've been to, Morty!")]
+    InvalidCodePoint(IntT),
+
+    #[error("\"{0}\" has to be exactly one character, Morty!")]
+    NotACharacter(String),
+
+    #[error("There's nothing between {0} and {1}, Morty! It's an empty dimension!")]
+    EmptyRange(IntT, IntT),
+
+    #[error("Morty, {0}({1})? That doesn't even make sense!")]
+    InvalidArgument(String, Value),
+
+    #[error("Wubba lubba dub dub! There's no {0} in there, Morty!")]
+    MissingKey(String),
+
+    #[error("{}", .0.message)]
+    Rethrown(Rc<ErrorValue>),
+
+    #[error("Your program threw {0}")]
+    Thrown(Value),
+
+    #[error("I looked everywhere for {0}, Morty. Every channel, every dimension. It's not there.")]
+    ModuleNotFound(String),
+
+    #[error("I can't make heads or tails of {module}, Morty: {error}")]
+    BrokenModule {
+        module: String,
+        error: grammar::ParseError,
+    },
+
+    #[error("Those modules import each other in a loop, Morty! {0}! It's a time paradox!")]
+    ImportCycle(String),
+
+    #[error("{0} doesn't have a {1} in it, Morty!")]
+    MissingExport(String, String),
+
+    #[error("You can't {0} out of a loop you were never in, Morty!")]
+    OutsideLoop(String),
+
+    #[error("There's no loop called {0} around here, Morty!")]
+    UnknownLabel(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorValue {
+    pub kind: String,
+    pub message: String,
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub value: Option<Value>,
+    span: Span,
 }
 
 impl<T> From<T> for EitherError
@@ -97,7 +179,7 @@ where
     ErrorKind: From<T>,
 {
     fn from(x: T) -> EitherError {
-        EitherError::NoContext(ErrorKind::from(x))
+        EitherError::NoContext(Box::new(ErrorKind::from(x)))
     }
 }
 
@@ -120,7 +202,8 @@ impl<T> ErrorKindExt<T> for std::result::Result<T, ErrorWithContext> {
 impl<T> ErrorKindExt<T> for std::result::Result<T, EitherError> {
     fn with_error_ctx(self, stmt: &Statement) -> Result<T, ErrorWithContext> {
         self.map_err(|e| match e {
-            EitherError::NoContext(e) => ErrorWithContext::new(e, stmt.clone()),
+            EitherError::NoContext(e) => ErrorWithContext::new(*e, stmt.clone()),
+            EitherError::WithSpan(e, span) => ErrorWithContext::spanned(*e, stmt.clone(), span),
             EitherError::WithContext(e) => e,
         })
     }
@@ -175,52 +258,331 @@ impl PartialEq for ErrorKind {
                 },
             ) => lib1 == lib2,
             (IncompatibleAbi(ver1), IncompatibleAbi(ver2)) => ver1 == ver2,
+            (LegacyAbi(ref s), LegacyAbi(ref o)) => s == o,
             (DylibReturnedNil, DylibReturnedNil) => true,
+            (UnportableValue(ref s), UnportableValue(ref o)) => s == o,
+            (CorruptPluginValue(ref s), CorruptPluginValue(ref o))
+            | (PluginError(ref s), PluginError(ref o)) => s == o,
+            (InvalidCodePoint(s), InvalidCodePoint(o)) => s == o,
+            (NotACharacter(ref s), NotACharacter(ref o)) => s == o,
+            (EmptyRange(s1, s2), EmptyRange(o1, o2)) => s1 == o1 && s2 == o2,
+            (InvalidArgument(ref sn, ref sv), InvalidArgument(ref on, ref ov)) => {
+                sn == on && sv == ov
+            }
+            (MissingKey(ref s), MissingKey(ref o)) => s == o,
+            (Rethrown(ref s), Rethrown(ref o)) => s == o,
+            (Thrown(ref s), Thrown(ref o)) => s == o,
+            (ModuleNotFound(ref s), ModuleNotFound(ref o))
+            | (ImportCycle(ref s), ImportCycle(ref o)) => s == o,
+            (
+                BrokenModule {
+                    module: ref smodule,
+                    error: ref serror,
+                },
+                BrokenModule {
+                    module: ref omodule,
+                    error: ref oerror,
+                },
+            ) => smodule == omodule && serror == oerror,
+            (MissingExport(ref sm, ref sn), MissingExport(ref om, ref on)) => sm == om && sn == on,
+            (OutsideLoop(ref s), OutsideLoop(ref o))
+            | (UnknownLabel(ref s), UnknownLabel(ref o)) => s == o,
+            _ => false,
+        }
+    }
+}
+
+impl ErrorKind {
+    pub fn name(&self) -> &str {
+        use self::ErrorKind::*;
+
+        match self {
+            UnknownVariable(_) => "UnknownVariable",
+            IndexUnindexable(_) => "IndexUnindexable",
+            SyntaxError(_) => "SyntaxError",
+            IndexOutOfBounds { .. } => "IndexOutOfBounds",
+            IOError(_) => "IOError",
+            UnexpectedType { .. } => "UnexpectedType",
+            LoadError(_) => "LoadError",
+            InvalidBinaryExpression(..) => "InvalidBinaryExpression",
+            InvalidArguments(..) => "InvalidArguments",
+            NoReturn(_) => "NoReturn",
+            NonFunctionCallInDylib(_) => "NonFunctionCallInDylib",
+            MissingAbiCompat { .. } => "MissingAbiCompat",
+            IncompatibleAbi(_) => "IncompatibleAbi",
+            LegacyAbi(_) => "LegacyAbi",
+            DylibReturnedNil => "DylibReturnedNil",
+            UnportableValue(_) => "UnportableValue",
+            CorruptPluginValue(_) => "CorruptPluginValue",
+            PluginError(_) => "PluginError",
+            InvalidCodePoint(_) => "InvalidCodePoint",
+            NotACharacter(_) => "NotACharacter",
+            EmptyRange(..) => "EmptyRange",
+            InvalidArgument(..) => "InvalidArgument",
+            MissingKey(_) => "MissingKey",
+            Rethrown(ref error) => &error.kind,
+            Thrown(_) => "Thrown",
+            ModuleNotFound(_) => "ModuleNotFound",
+            BrokenModule { .. } => "BrokenModule",
+            ImportCycle(_) => "ImportCycle",
+            MissingExport(..) => "MissingExport",
+            OutsideLoop(_) => "OutsideLoop",
+            UnknownLabel(_) => "UnknownLabel",
+        }
+    }
+
+    /// Whether this came from a `throw`, even if it was caught and rethrown.
+    fn is_thrown(&self) -> bool {
+        match self {
+            ErrorKind::Thrown(_) => true,
+            ErrorKind::Rethrown(error) => error.kind == "Thrown",
             _ => false,
         }
     }
 }
 
+impl ErrorValue {
+    pub fn new(error: &ErrorWithContext, files: &Files) -> Rc<Self> {
+        if let ErrorKind::Rethrown(ref error) = error.kind() {
+            return Rc::clone(error);
+        }
+
+        let span = error.span();
+        let value = match error.kind() {
+            ErrorKind::Thrown(ref value) => Some(value.clone()),
+            _ => None,
+        };
+
+        Rc::new(Self {
+            kind: error.kind().name().into(),
+            message: error.kind().to_string(),
+            file: files.name(span.file).into(),
+            line: span.line,
+            column: span.column,
+            value,
+            span,
+        })
+    }
+
+    pub fn field(&self, name: &str) -> SwResult<Value> {
+        match name {
+            "kind" => Ok(self.kind.clone().into()),
+            "message" => Ok(self.message.clone().into()),
+            "file" => Ok(self.file.clone().into()),
+            "line" => Ok(Value::Int(self.line as value::IntT)),
+            "column" => Ok(Value::Int(self.column as value::IntT)),
+            "value" => self
+                .value
+                .clone()
+                .ok_or_else(|| ErrorKind::MissingKey(name.into()).into()),
+            _ => Err(ErrorKind::MissingKey(name.into()).into()),
+        }
+    }
+}
+
+pub(crate) fn rethrow(value: Value) -> EitherError {
+    match value {
+        Value::Error(error) => {
+            let span = error.span;
+            EitherError::WithSpan(Box::new(ErrorKind::Rethrown(error)), span)
+        }
+        value => ErrorKind::UnexpectedType {
+            expected: value::Type::Error,
+            actual: value.get_type(),
+        }
+        .into(),
+    }
+}
+
 impl ErrorWithContext {
     pub fn new(kind: ErrorKind, place: Statement) -> Self {
-        Self { kind, place }
+        let span = place.span();
+        Self::spanned(kind, place, span)
     }
 
-    pub fn full_panic_message(&self, filename: &str) -> String {
-        let mut f = String::new();
+    pub fn spanned(kind: ErrorKind, place: Statement, span: Span) -> Self {
+        Self {
+            context: Box::new(Context { place, span, kind }),
+        }
+    }
 
-        let quote = random_quote();
+    pub fn kind(&self) -> &ErrorKind {
+        &self.context.kind
+    }
 
-        writeln!(f, "{}", filename).unwrap();
+    pub fn place(&self) -> &Statement {
+        &self.context.place
+    }
 
-        let source_part = self.place.get_source(&filename).unwrap();
+    pub fn span(&self) -> Span {
+        self.context.span
+    }
+}
+
+impl EitherError {
+    pub fn kind(&self) -> &ErrorKind {
+        match self {
+            EitherError::WithContext(e) => e.kind(),
+            EitherError::NoContext(kind) | EitherError::WithSpan(kind, _) => kind,
+        }
+    }
 
-        for c in anyhow::Chain::new(&self.kind) {
-            writeln!(f, "{}", c).unwrap();
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            EitherError::WithContext(e) => Some(e.span()),
+            EitherError::WithSpan(_, span) => Some(*span),
+            EitherError::NoContext(_) => None,
         }
+    }
 
-        writeln!(
-            f,
-            r#"
+    pub fn at(self, span: Span) -> Self {
+        match self {
+            EitherError::NoContext(kind) => EitherError::WithSpan(kind, span),
+            e => e,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    #[error("Couldn't read {file}")]
+    Io {
+        file: String,
+        #[source]
+        error: io::Error,
+    },
+
+    #[error("SYNTAX ERROR: {file}:{line}:{column}")]
+    Syntax {
+        file: String,
+        line: usize,
+        column: usize,
+        source_line: String,
+        #[source]
+        error: Box<grammar::ParseError>,
+    },
+
+    #[error("{file}:{line}:{column}: {}", .error.kind())]
+    Runtime {
+        file: String,
+        line: usize,
+        column: usize,
+        code_frame: String,
+        error: Box<EitherError>,
+    },
+}
+
+impl RunError {
+    pub fn syntax(file: &str, source: &str, error: grammar::ParseError) -> Self {
+        let source_line = source
+            .lines()
+            .nth(error.location.line - 1)
+            .unwrap_or("")
+            .to_string();
+
+        RunError::Syntax {
+            file: file.into(),
+            line: error.location.line,
+            column: error.location.column,
+            source_line,
+            error: Box::new(error),
+        }
+    }
+
+    /// An error raised while running `file`. It is reported where it was
+    /// raised, which can be in another file, like a module or a function
+    /// defined by an earlier run.
+    pub fn runtime(files: &Files, file: FileId, error: EitherError) -> Self {
+        let (file, line, column, code_frame) = match error.span() {
+            Some(span) => (
+                span.file,
+                span.line,
+                span.column,
+                span.code_frame(files.source(span.file)),
+            ),
+            None => (file, 0, 0, String::new()),
+        };
+
+        RunError::Runtime {
+            file: files.name(file).into(),
+            line,
+            column,
+            code_frame,
+            error: Box::new(error),
+        }
+    }
+
+    pub fn report(&self) -> String {
+        match self {
+            RunError::Io { error, .. } => format!("{}: {}", self, error),
+            RunError::Syntax {
+                file,
+                line,
+                column,
+                source_line,
+                ..
+            } => format!(
+                "SYNTAX ERROR: {}:{}\n{}\n{}^",
+                file,
+                line,
+                source_line,
+                " ".repeat(column - 1)
+            ),
+            RunError::Runtime {
+                file,
+                line,
+                column,
+                code_frame,
+                error,
+            } => {
+                let location = format!("{}:{}:{}", file, line, column);
+                rickdiculous_report(&location, error.kind(), code_frame)
+            }
+        }
+    }
+}
+
+fn rickdiculous_report(location: &str, kind: &ErrorKind, code_frame: &str) -> String {
+    let mut f = String::new();
+
+    let quote = random_quote();
+
+    // Only a report knows nothing caught the error on its way out.
+    let message = if kind.is_thrown() {
+        format!(
+            "{} and nobody caught it. Nobody exists on purpose, Morty.",
+            kind
+        )
+    } else {
+        kind.to_string()
+    };
+
+    writeln!(f, "{}", location).unwrap();
+    writeln!(f, "{}", message).unwrap();
+
+    for c in anyhow::Chain::new(kind).skip(1) {
+        writeln!(f, "{}", c).unwrap();
+    }
+
+    writeln!(
+        f,
+        r#"
     You made a Rickdiculous mistake:
 
     {}
+
     {}
 
     {}
 
     "#,
-            source_part, self, quote
-        )
-        .unwrap();
+        code_frame.replace('\n', "\n    "),
+        message,
+        quote
+    )
+    .unwrap();
 
-        f
-    }
-
-    pub fn panic(&self, source: &str) {
-        println!("{}", self.full_panic_message(source));
-        process::exit(1);
-    }
+    f
 }
 
 fn random_quote() -> &'static str {
@@ -1,22 +1,34 @@
 use crate::{
-    error::{ErrorKind, SwResult},
+    error::{EitherError, ErrorKind, SwResult},
     grammar,
+    span::Span,
     state::State,
+    statement::Statement,
+    symbols::Ident,
     value::{self, IntT, Value},
     Operator,
 };
-use std::borrow;
+use std::{borrow, cmp};
+
+#[derive(Debug, Clone)]
+pub struct Expression {
+    span: Span,
+    pub kind: ExpressionKind,
+}
 
 #[derive(Debug, PartialEq, Clone)]
-pub enum Expression {
-    Variable(String),
+pub enum ExpressionKind {
+    Variable(Ident),
     OpExp(Box<Expression>, Operator, Box<Expression>),
     Value(Value),
-    ListIndex(String, Box<Expression>),
-    ListLength(String),
+    ListIndex(Box<Expression>, Box<Expression>),
+    ListLength(Ident),
     Not(Box<Expression>),
     Eval(Box<Expression>),
-    FunctionCall(String, Vec<Expression>),
+    FunctionCall(Ident, Vec<Expression>),
+    Call(Box<Expression>, Vec<Expression>),
+    Lambda(Vec<Ident>, Vec<Statement>),
+    List(Vec<Expression>),
 }
 
 impl<T> From<T> for Expression
@@ -24,95 +36,201 @@ where
     T: Into<Value>,
 {
     fn from(fr: T) -> Self {
-        Expression::Value(fr.into())
+        ExpressionKind::Value(fr.into()).into()
+    }
+}
+
+impl From<ExpressionKind> for Expression {
+    fn from(kind: ExpressionKind) -> Self {
+        Self::with_span(kind, Span::default())
     }
 }
 
 impl Expression {
+    pub fn with_span(kind: ExpressionKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
     pub fn evaluate<'a, 'b: 'a>(&'a self, state: &'b State) -> SwResult<borrow::Cow<'a, Value>> {
-        match *self {
-            Expression::Variable(ref name) => state.get(name).map(borrow::Cow::Borrowed),
-            Expression::OpExp(ref left_exp, ref operator, ref right_exp) => {
-                let left = left_exp.evaluate(state)?;
-                let right = right_exp.evaluate(state)?;
-                let result = match *operator {
-                    Operator::Add => left.add(&right),
-                    Operator::Subtract => left.subtract(&right),
-                    Operator::Multiply => left.multiply(&right),
-                    Operator::Divide => left.divide(&right),
-                    Operator::Equality => Ok(left.equals(&right)),
-                    Operator::LessThan => left.less_than(&right),
-                    Operator::GreaterThan => left.greater_than(&right),
-                    Operator::LessThanEqual => left.less_than_equal(&right),
-                    Operator::GreaterThanEqual => left.greater_than_equal(&right),
-                    Operator::ShiftLeft => left.shift_left(&right),
-                    Operator::ShiftRight => left.shift_right(&right),
-                    Operator::And => left.and(&right),
-                    Operator::Or => left.or(&right),
-                    Operator::Modulus => left.modulus(&right),
-                };
-
-                result.map(borrow::Cow::Owned)
+        self.evaluate_kind(state).map_err(|e| e.at(self.span))
+    }
+
+    fn evaluate_kind<'a, 'b: 'a>(&'a self, state: &'b State) -> SwResult<borrow::Cow<'a, Value>> {
+        match self.kind {
+            ExpressionKind::Variable(name) => Ok(borrow::Cow::Owned(state.get(name)?.clone())),
+            ExpressionKind::OpExp(ref left_exp, ref operator, ref right_exp) => {
+                let apply =
+                    |left: &Value| right_exp.with_value(state, |right| operator.apply(left, right));
+
+                // The left side can only stay borrowed if working out the
+                // right one can't run code that changes it.
+                if right_exp.is_read() {
+                    left_exp.with_value(state, apply)
+                } else {
+                    apply(&*left_exp.evaluate(state)?)
+                }
+                .map(borrow::Cow::Owned)
             }
-            Expression::Value(ref v) => Ok(borrow::Cow::Borrowed(v)),
-            Expression::ListIndex(ref var_name, ref e) => state.list_index(var_name, e),
-            Expression::Not(ref e) => e.evaluate(state)?.not().map(borrow::Cow::Owned),
-            Expression::ListLength(ref var_name) => {
-                let value = state.get(var_name)?;
-                match *value {
-                    Value::List(ref list) => Ok(borrow::Cow::Owned(Value::Int(list.len() as IntT))),
-                    Value::Str(ref s) => Ok(borrow::Cow::Owned(Value::Int(s.len() as IntT))),
-                    _ => Err(ErrorKind::IndexUnindexable(value.get_type()).into()),
+            ExpressionKind::Value(ref v) => Ok(borrow::Cow::Borrowed(v)),
+            ExpressionKind::ListIndex(ref target, ref index) => match target.kind {
+                ExpressionKind::Variable(name) => state.list_index(name, index),
+                _ if index.is_read() => {
+                    self.with_value(state, |item| Ok(borrow::Cow::Owned(item.clone())))
+                }
+                _ => {
+                    let target = target.evaluate(state)?;
+                    let index = index.evaluate(state)?;
+                    target.index(&index).map(borrow::Cow::Owned)
                 }
+            },
+            ExpressionKind::Not(ref e) => e.evaluate(state)?.not().map(borrow::Cow::Owned),
+            ExpressionKind::ListLength(var_name) => {
+                list_length(&*state.get(var_name)?).map(borrow::Cow::Owned)
             }
-            Expression::Eval(ref exp) => {
+            ExpressionKind::Eval(ref exp) => {
                 let inner_val = exp.evaluate(state)?;
-                if let Value::Str(ref inner) = *inner_val {
-                    match grammar::expression(inner) {
-                        Ok(inner_evaled) => inner_evaled
-                            .evaluate(state)
-                            .map(borrow::Cow::into_owned)
-                            .map(borrow::Cow::Owned),
-                        Err(s) => Err(ErrorKind::SyntaxError(s).into()),
-                    }
-                } else {
-                    Err(ErrorKind::UnexpectedType {
-                        expected: value::Type::Str,
-                        actual: inner_val.get_type(),
-                    }
-                    .into())
-                }
+                eval_value(&inner_val, state).map(borrow::Cow::Owned)
             }
-            Expression::FunctionCall(ref name, ref args) => {
+            ExpressionKind::FunctionCall(name, ref args) => {
                 state.call_function(name, args).map(borrow::Cow::Owned)
             }
+            ExpressionKind::Call(ref callee, ref args) => {
+                state.call_expression(callee, args).map(borrow::Cow::Owned)
+            }
+            ExpressionKind::Lambda(ref params, ref body) => {
+                Ok(borrow::Cow::Owned(state.closure(params, body)))
+            }
+            ExpressionKind::List(ref items) => {
+                let mut list = Vec::with_capacity(items.len());
+                for item in items {
+                    list.push(item.evaluate(state)?.into_owned());
+                }
+
+                Ok(borrow::Cow::Owned(Value::List(list)))
+            }
+        }
+    }
+
+    /// Runs `f` on the value of this expression. Variables, and the items of
+    /// lists and maps they hold, are borrowed instead of copied.
+    pub fn with_value<T, F>(&self, state: &State, mut f: F) -> SwResult<T>
+    where
+        F: FnMut(&Value) -> SwResult<T>,
+    {
+        self.borrow_value(state, &mut f)
+    }
+
+    fn borrow_value<T>(
+        &self,
+        state: &State,
+        f: &mut dyn FnMut(&Value) -> SwResult<T>,
+    ) -> SwResult<T> {
+        match self.kind {
+            ExpressionKind::Variable(name) => {
+                let value = state.get(name).map_err(|e| e.at(self.span))?;
+                f(&value)
+            }
+            // Like `State::list_index`, the index is worked out first.
+            ExpressionKind::ListIndex(ref target, ref index) if index.is_read() => {
+                let index = index.evaluate(state)?;
+                target.borrow_value(state, &mut |target| {
+                    let item = target.item(&index).map_err(|e| e.at(self.span))?;
+                    f(&item)
+                })
+            }
+            _ => f(&*self.evaluate(state)?),
+        }
+    }
+
+    /// Whether working this expression out only reads variables, without
+    /// running any code that could change them.
+    fn is_read(&self) -> bool {
+        match self.kind {
+            ExpressionKind::Variable(_)
+            | ExpressionKind::Value(_)
+            | ExpressionKind::ListLength(_) => true,
+            ExpressionKind::ListIndex(ref target, ref index) => target.is_read() && index.is_read(),
+            _ => false,
         }
     }
 
     pub fn try_bool(&self, state: &mut State) -> SwResult<bool> {
-        let value = self.evaluate(state)?;
-        if let Value::Bool(x) = *value {
-            Ok(x)
-        } else {
-            Err(ErrorKind::UnexpectedType {
+        self.with_value(state, |value| match *value {
+            Value::Bool(x) => Ok(x),
+            _ => Err(ErrorKind::UnexpectedType {
                 expected: value::Type::Bool,
                 actual: value.get_type(),
             }
-            .into())
-        }
+            .into()),
+        })
     }
 
     pub fn try_int(&self, state: &mut State) -> SwResult<IntT> {
-        let value = self.evaluate(state)?;
-        if let Value::Int(x) = *value {
-            Ok(x)
-        } else {
-            Err(ErrorKind::UnexpectedType {
+        self.with_value(state, |value| match *value {
+            Value::Int(x) => Ok(x),
+            _ => Err(ErrorKind::UnexpectedType {
                 expected: value::Type::Int,
                 actual: value.get_type(),
             }
-            .into())
+            .into()),
+        })
+    }
+}
+
+impl Operator {
+    pub fn apply(&self, left: &Value, right: &Value) -> SwResult<Value> {
+        match *self {
+            Operator::Add => left.add(right),
+            Operator::Subtract => left.subtract(right),
+            Operator::Multiply => left.multiply(right),
+            Operator::Divide => left.divide(right),
+            Operator::Equality => Ok(left.equals(right)),
+            Operator::LessThan => left.less_than(right),
+            Operator::GreaterThan => left.greater_than(right),
+            Operator::LessThanEqual => left.less_than_equal(right),
+            Operator::GreaterThanEqual => left.greater_than_equal(right),
+            Operator::ShiftLeft => left.shift_left(right),
+            Operator::ShiftRight => left.shift_right(right),
+            Operator::And => left.and(right),
+            Operator::Or => left.or(right),
+            Operator::Modulus => left.modulus(right),
+        }
+    }
+}
+
+pub(crate) fn list_length(value: &Value) -> SwResult<Value> {
+    match *value {
+        Value::List(ref list) => Ok(Value::Int(list.len() as IntT)),
+        Value::Str(ref s) => Ok(Value::Int(s.chars().count() as IntT)),
+        Value::Map(ref m) => Ok(Value::Int(m.len() as IntT)),
+        _ => Err(ErrorKind::IndexUnindexable(value.get_type()).into()),
+    }
+}
+
+pub(crate) fn eval_value(value: &Value, state: &State) -> SwResult<Value> {
+    if let Value::Str(ref inner) = *value {
+        match grammar::expression(inner) {
+            // Spans inside the evaluated string don't point into the program, so
+            // errors are reported at the `{}` expression instead.
+            Ok(inner_evaled) => inner_evaled
+                .evaluate(state)
+                .map(borrow::Cow::into_owned)
+                .map_err(|e| match e {
+                    EitherError::WithSpan(kind, _) => EitherError::NoContext(kind),
+                    e => e,
+                }),
+            Err(s) => Err(ErrorKind::SyntaxError(s).into()),
+        }
+    } else {
+        Err(ErrorKind::UnexpectedType {
+            expected: value::Type::Str,
+            actual: value.get_type(),
         }
+        .into())
     }
 }
 
@@ -127,16 +245,16 @@ impl Expression {
 
     pub fn variable<S>(name: S) -> Expression
     where
-        S: Into<String>,
+        S: Into<Ident>,
     {
-        Expression::Variable(name.into())
+        ExpressionKind::Variable(name.into()).into()
     }
 
     pub fn list_length<S>(name: S) -> Expression
     where
-        S: Into<String>,
+        S: Into<Ident>,
     {
-        Expression::ListLength(name.into())
+        ExpressionKind::ListLength(name.into()).into()
     }
 
     pub fn operator<L, R>(left: L, op: Operator, right: R) -> Expression
@@ -144,35 +262,62 @@ impl Expression {
         L: Into<Expression>,
         R: Into<Expression>,
     {
-        Expression::OpExp(Box::new(left.into()), op, Box::new(right.into()))
+        ExpressionKind::OpExp(Box::new(left.into()), op, Box::new(right.into())).into()
     }
 
     pub fn not<E>(expr: E) -> Expression
     where
         E: Into<Expression>,
     {
-        Expression::Not(Box::new(expr.into()))
+        ExpressionKind::Not(Box::new(expr.into())).into()
     }
 
     pub fn eval<E>(expr: E) -> Expression
     where
         E: Into<Expression>,
     {
-        Expression::Eval(Box::new(expr.into()))
+        ExpressionKind::Eval(Box::new(expr.into())).into()
     }
 
     pub fn list_index<S, E>(name: S, index: E) -> Expression
     where
-        S: Into<String>,
+        S: Into<Ident>,
         E: Into<Expression>,
     {
-        Expression::ListIndex(name.into(), Box::new(index.into()))
+        Self::index(Self::variable(name), index)
+    }
+
+    pub fn index<T, E>(target: T, index: E) -> Expression
+    where
+        T: Into<Expression>,
+        E: Into<Expression>,
+    {
+        ExpressionKind::ListIndex(Box::new(target.into()), Box::new(index.into())).into()
+    }
+
+    pub fn list(items: Vec<Expression>) -> Expression {
+        ExpressionKind::List(items).into()
+    }
+
+    pub fn call<E>(callee: E, args: Vec<Expression>) -> Expression
+    where
+        E: Into<Expression>,
+    {
+        ExpressionKind::Call(Box::new(callee.into()), args).into()
     }
 
     pub fn value<V>(val: V) -> Expression
     where
         V: Into<Value>,
     {
-        Expression::Value(val.into())
+        ExpressionKind::Value(val.into()).into()
+    }
+}
+
+/// Expressions are equal when they are the same expression, wherever they were
+/// written. Compare their `span()`s to check where that was.
+impl cmp::PartialEq for Expression {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind
     }
 }
@@ -1,13 +1,60 @@
-use crate::expression::Expression;
-use crate::statement::{Statement, StatementKind};
+// peg expands `precedence!` actions into closures that are called on the spot.
+#![allow(clippy::redundant_closure_call)]
+
+use crate::expression::{Expression, ExpressionKind};
+use crate::span::{FileId, LineIndex};
+use crate::statement::{Imports, Iterable, Statement, StatementKind};
+use crate::symbols::Ident;
 use crate::value::{string_parse, FloatT, IntT, Value};
 use crate::Operator;
 
-pub use self::grammar::*;
-
 pub type ParseError = peg::error::ParseError<peg_runtime::str::LineCol>;
 
-peg::parser! {grammar grammar() for str {
+macro_rules! entry_points {
+    ( $( $rule:ident -> $ty:ty ),* ) => {
+        $(
+            pub fn $rule(input: &str) -> Result<$ty, ParseError> {
+                parser::$rule(input, &LineIndex::new(FileId::default(), input))
+            }
+        )*
+    };
+}
+
+entry_points! {
+    value -> Value,
+    operator -> Operator,
+    block -> Vec<Statement>,
+    file -> Vec<Statement>,
+    params -> Vec<Ident>,
+    statement_kind -> StatementKind,
+    statement -> Statement,
+    expression -> Expression,
+    args -> Vec<Expression>
+}
+
+pub fn parse(input: &str, file: FileId) -> Result<Vec<Statement>, ParseError> {
+    parser::file(input, &LineIndex::new(file, input))
+}
+
+pub fn parse_expression(input: &str, file: FileId) -> Result<Expression, ParseError> {
+    parser::expression(input, &LineIndex::new(file, input))
+}
+
+enum Postfix {
+    Call(Vec<Expression>),
+    Index(Expression),
+}
+
+/// Joins two operands into one expression spanning both of them.
+fn binary(lines: &LineIndex, left: Expression, op: Operator, right: Expression) -> Expression {
+    let span = lines.span(left.span().start, right.span().end());
+    Expression::with_span(
+        ExpressionKind::OpExp(Box::new(left), op, Box::new(right)),
+        span,
+    )
+}
+
+peg::parser! {grammar parser(lines: &LineIndex) for str {
 
     rule string_inquotes() -> String
         = s:$((!['"'][_])*) { string_parse(s) }
@@ -28,8 +75,8 @@ peg::parser! {grammar grammar() for str {
         / "rick" { Value::Bool(true) }
         / "morty" { Value::Bool(false) }
 
-    rule identifier() -> String
-        = s:$(['a'..='z' | 'A'..='Z' | '_'] ['a'..='z' | 'A'..='Z' | '0'..='9' | '_']*) { s.to_string() }
+    rule identifier() -> Ident
+        = s:$(['a'..='z' | 'A'..='Z' | '_'] ['a'..='z' | 'A'..='Z' | '0'..='9' | '_']*) { Ident::intern(s) }
 
     pub rule operator() -> Operator
         = "+" { Operator::Add }
@@ -47,12 +94,18 @@ peg::parser! {grammar grammar() for str {
         / "or" { Operator::Or }
         / "and" { Operator::And }
 
+    rule ident_char()
+        = ['a'..='z' | 'A'..='Z' | '0'..='9' | '_']
+
     rule WS()
         = [' ' | '\t']+
 
     rule ws()
         = [' ' | '\t']*
 
+    rule gap()
+        = [' ' | '\t' | '\r' | '\n']*
+
     rule comma()
         = ws() "," ws()
 
@@ -65,17 +118,18 @@ peg::parser! {grammar grammar() for str {
     pub rule file() -> Vec<Statement>
         = l:line()+ ws() { l }
 
-    pub rule newline()
+    rule newline()
         = ws() "\n" / ws() "\r\n"
 
-    pub rule params() -> Vec<String>
+    pub rule params() -> Vec<Ident>
         = "(" is:identifier() ** comma() ")" { is }
 
     pub rule statement_kind() -> StatementKind
-        = "squanch" WS() i:identifier() ws() "[" ws() e:expression() ws() "]" { StatementKind::ListDelete(i, e) }
+        = "squanch" WS() i:identifier() is:index()+ { StatementKind::ListDelete(i, is) }
         / i:identifier() WS() "on a cob" { StatementKind::ListNew(i) }
+        / i:identifier() WS() "on a plumbus" { StatementKind::MapNew(i) }
         / i:identifier() WS() "assimilate" WS() e:expression() { StatementKind::ListAppend(i, e) }
-        / i:identifier() ws() "[" ws() v:expression() ws() "]" WS() "squanch" WS() e:expression() { StatementKind::ListAssign(i, v, e) }
+        / i:identifier() is:index()+ WS() "squanch" WS() e:expression() { StatementKind::ListAssign(i, is, e) }
         / n:identifier() ws() p:params() ws() b:block() { StatementKind::Function(n, p, b) }
         / "squanch" WS() i:identifier() { StatementKind::Delete(i) }
         / i:identifier() WS() "squanch" WS() e:expression() { StatementKind::Assignment(i, e) }
@@ -84,30 +138,121 @@ peg::parser! {grammar grammar() for str {
         / "if" WS() e:expression() WS() i_bod:block() ws() "else" WS() e_bod:block() { StatementKind::If(e, i_bod, Option::Some(e_bod)) }
         / "if" WS() e:expression() WS() s:block() { StatementKind::If(e, s, Option::None) }
         / "while" WS() e:expression() WS() b:block() { StatementKind::While(e, b) }
+        / "for" WS() i:identifier() WS() "in" WS() it:iterable() WS() b:block() { StatementKind::For(i, it, b) }
+        / l:identifier() ":" WS() s:statement() {?
+            match s.kind {
+                StatementKind::While(..) | StatementKind::For(..) => Ok(StatementKind::Labeled(l, Box::new(s))),
+                _ => Err("a loop"),
+            }
+        }
         / "portal gun" WS() i:identifier() { StatementKind::Input(i) }
-        / "normal plan" ws() try_block:block() ws() "plan for failure" ws() catch:block() { StatementKind::Catch(try_block, catch) }
+        / "normal plan" ws() try_block:block() ws() "plan for failure" e:(WS() e:identifier() { e })? ws() catch:block() { StatementKind::Catch(try_block, e, catch) }
+        / "rethrow" WS() e:expression() { StatementKind::Rethrow(e) }
+        / "throw" WS() e:expression() { StatementKind::Throw(e) }
+        / e:call() { StatementKind::Call(e) }
         / i:identifier() a:args() { StatementKind::FunctionCall(i, a) }
+        / "global" WS() is:identifier() ++ comma() { StatementKind::Global(is) }
+        / "nonlocal" WS() is:identifier() ++ comma() { StatementKind::Nonlocal(is) }
         / "return" WS() e:expression() { StatementKind::Return(e) }
         / "microverse" WS() lib:string() WS() funcs:block() { StatementKind::DylibLoad(lib, funcs) }
+        / "break" !ident_char() l:(WS() l:identifier() { l })? { StatementKind::Break(l) }
+        / "continue" !ident_char() l:(WS() l:identifier() { l })? { StatementKind::Continue(l) }
+        / "interdimensional cable" WS() path:string() i:imports() { StatementKind::Import(path, i) }
+
+    rule iterable() -> Iterable
+        = start:expression() WS() "up to" WS() end:expression() { Iterable::Range(start, end) }
+        / e:expression() { Iterable::Each(e) }
+
+    rule imports() -> Imports
+        = WS() "as" WS() i:identifier() { Imports::Module(Some(i)) }
+        / WS() "showing" WS() is:identifier() ++ comma() { Imports::Names(is) }
+        / "" { Imports::Module(None) }
+
+    rule returned() -> Statement
+        = start:position!() e:expression() end:position!() { Statement::new(StatementKind::Return(e), lines.span(start, end)) }
 
     pub rule statement() -> Statement
-        = start:position!() s:statement_kind() end:position!() { Statement::new(s, start, end) }
+        = start:position!() s:statement_kind() end:position!() { Statement::new(s, lines.span(start, end)) }
 
     pub rule expression() -> Expression
-        = "{" ws() e:expression() ws() "}" { Expression::Eval(Box::new(e)) }
-        / "(" ws() e1:expression() ws() o:operator() ws() e2:expression() ws() ")" { Expression::OpExp(Box::new(e1), o, Box::new(e2)) }
-        / "(" ws() e:expression1() ws() ")" { e }
+        = precedence! {
+            l:(@) WS() "or" !ident_char() ws() r:@ { binary(lines, l, Operator::Or, r) }
+            --
+            l:(@) WS() "and" !ident_char() ws() r:@ { binary(lines, l, Operator::And, r) }
+            --
+            l:(@) ws() o:comparison() ws() r:@ { binary(lines, l, o, r) }
+            --
+            l:(@) ws() "schwift>" ws() r:@ { binary(lines, l, Operator::ShiftRight, r) }
+            l:(@) ws() "<schwift" ws() r:@ { binary(lines, l, Operator::ShiftLeft, r) }
+            --
+            l:(@) ws() "+" ws() r:@ { binary(lines, l, Operator::Add, r) }
+            l:(@) ws() "-" ws() r:@ { binary(lines, l, Operator::Subtract, r) }
+            --
+            l:(@) ws() "*" ws() r:@ { binary(lines, l, Operator::Multiply, r) }
+            l:(@) ws() "/" ws() r:@ { binary(lines, l, Operator::Divide, r) }
+            l:(@) ws() "%" ws() r:@ { binary(lines, l, Operator::Modulus, r) }
+            --
+            e:unary() { e }
+        }
+
+    rule comparison() -> Operator
+        = "==" { Operator::Equality }
+        / "moresquanch" !ident_char() { Operator::GreaterThanEqual }
+        / "lesssquanch" !ident_char() { Operator::LessThanEqual }
+        / "more" !ident_char() { Operator::GreaterThan }
+        / "less" !ident_char() { Operator::LessThan }
+
+    rule unary() -> Expression
+        = start:position!() "!" ws() e:unary() end:position!() { Expression::with_span(ExpressionKind::Not(Box::new(e)), lines.span(start, end)) }
+        / postfix()
+
+    rule postfix() -> Expression
+        = start:position!() e:primary() end:position!() ops:(o:postfix_op() end:position!() { (o, end) })* {
+            let mut e = Expression::with_span(e, lines.span(start, end));
+            for (op, end) in ops {
+                let kind = match op {
+                    Postfix::Call(args) => ExpressionKind::Call(Box::new(e), args),
+                    Postfix::Index(index) => ExpressionKind::ListIndex(Box::new(e), Box::new(index)),
+                };
+                e = Expression::with_span(kind, lines.span(start, end));
+            }
+            e
+        }
+
+    rule postfix_op() -> Postfix
+        = a:args() { Postfix::Call(a) }
+        / i:index() { Postfix::Index(i) }
+
+    rule index() -> Expression
+        = ws() "[" ws() e:expression() ws() "]" { e }
+
+    rule call() -> Expression
+        = e:expression() {?
+            match e.kind {
+                ExpressionKind::Call(_, _) => Ok(e),
+                _ => Err("a function call"),
+            }
+        }
+
+    rule primary() -> ExpressionKind
+        = "{" ws() e:expression() ws() "}" { ExpressionKind::Eval(Box::new(e)) }
+        / "meeseeks" ws() p:params() ws() b:block() { ExpressionKind::Lambda(p, b) }
+        / "meeseeks" ws() p:params() ws() "->" ws() r:returned() { ExpressionKind::Lambda(p, vec![r]) }
+        / "(" ws() e:expression() ws() ")" { e.kind }
+        / "[" gap() items:list_items() gap() "]" { ExpressionKind::List(items) }
         / expression1()
 
+    rule list_items() -> Vec<Expression>
+        = items:expression() ++ (gap() "," gap()) (gap() ",")? { items }
+        / "" { Vec::new() }
+
     pub rule args() -> Vec<Expression>
         = "(" exprs:expression() ** comma() ")" { exprs }
 
-    rule expression1() -> Expression
-        = i:identifier() ws() "[" ws() e:expression() ws() "]" { Expression::ListIndex(i, Box::new(e)) }
-        / i:identifier() a:args() { Expression::FunctionCall(i, a) }
-        / v:value() { Expression::Value(v) }
-        / i:identifier() WS() "squanch" { Expression::ListLength(i) }
-        / i:identifier() { Expression::Variable(i) }
-        / "!" ws() e:expression() { Expression::Not(Box::new(e)) }
+    rule expression1() -> ExpressionKind
+        = i:identifier() a:args() { ExpressionKind::FunctionCall(i, a) }
+        / v:value() { ExpressionKind::Value(v) }
+        / i:identifier() WS() "squanch" { ExpressionKind::ListLength(i) }
+        / i:identifier() { ExpressionKind::Variable(i) }
 
 }}
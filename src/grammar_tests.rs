@@ -1,7 +1,8 @@
 use crate::{
-    expression::Expression as Exp,
+    expression::{Expression as Exp, ExpressionKind as ExpKind},
     grammar,
-    statement::{Statement, StatementKind as Kind},
+    span::Files,
+    statement::{Imports, Statement, StatementKind as Kind},
     value::Value,
     Operator as Op,
 };
@@ -34,6 +35,12 @@ fn test_list_instantiation() {
     assert_eq!(l, Kind::new_list("foobar"));
 }
 
+#[test]
+fn test_map_instantiation() {
+    let l = grammar::statement_kind("squanchy on a plumbus").unwrap();
+    assert_eq!(l, Kind::new_map("squanchy"));
+}
+
 #[test]
 fn test_list_instantiation_statement() {
     let l = grammar::statement_kind("foobar on a cob").unwrap();
@@ -261,6 +268,128 @@ fn test_multiple_operator_expressions_parenthesis() {
     );
 }
 
+#[test]
+fn test_precedence() {
+    let x = || Exp::variable("x");
+    let y = || Exp::variable("y");
+
+    assert_eq!(
+        grammar::expression("1 + 2 * 3").unwrap(),
+        Exp::operator(1, Op::Add, Exp::operator(2, Op::Multiply, 3))
+    );
+
+    assert_eq!(
+        grammar::expression("x % 2 == 0 or x schwift> 1 less y and !y").unwrap(),
+        Exp::operator(
+            Exp::operator(Exp::operator(x(), Op::Modulus, 2), Op::Equality, 0),
+            Op::Or,
+            Exp::operator(
+                Exp::operator(Exp::operator(x(), Op::ShiftRight, 1), Op::LessThan, y()),
+                Op::And,
+                Exp::not(y())
+            )
+        )
+    );
+
+    assert_eq!(
+        grammar::expression("x[0] <schwift 2 + y moresquanch f(1) * 3").unwrap(),
+        Exp::operator(
+            Exp::operator(
+                Exp::list_index("x", 0),
+                Op::ShiftLeft,
+                Exp::operator(2, Op::Add, y())
+            ),
+            Op::GreaterThanEqual,
+            Exp::operator(
+                ExpKind::FunctionCall("f".into(), vec![1.into()]),
+                Op::Multiply,
+                3
+            )
+        )
+    );
+}
+
+#[test]
+fn test_left_associativity() {
+    assert_eq!(
+        grammar::expression("10 - 4 - 3").unwrap(),
+        Exp::operator(Exp::operator(10, Op::Subtract, 4), Op::Subtract, 3)
+    );
+
+    assert_eq!(
+        grammar::expression("8 / 4 * 2").unwrap(),
+        Exp::operator(Exp::operator(8, Op::Divide, 4), Op::Multiply, 2)
+    );
+}
+
+#[test]
+fn test_parentheses_override_precedence() {
+    assert_eq!(
+        grammar::expression("(1 + 2) * 3").unwrap(),
+        Exp::operator(Exp::operator(1, Op::Add, 2), Op::Multiply, 3)
+    );
+
+    assert_eq!(
+        grammar::expression("!(x and y) or (x == (y + 1))").unwrap(),
+        Exp::operator(
+            Exp::not(Exp::operator(
+                Exp::variable("x"),
+                Op::And,
+                Exp::variable("y")
+            )),
+            Op::Or,
+            Exp::operator(
+                Exp::variable("x"),
+                Op::Equality,
+                Exp::operator(Exp::variable("y"), Op::Add, 1)
+            )
+        )
+    );
+}
+
+#[test]
+fn test_unparenthesised_statements() {
+    assert_eq!(
+        grammar::statement_kind("x squanch y * 2 + 1").unwrap(),
+        Kind::assignment(
+            "x",
+            Exp::operator(
+                Exp::operator(Exp::variable("y"), Op::Multiply, 2),
+                Op::Add,
+                1
+            )
+        )
+    );
+
+    assert_eq!(
+        grammar::statement_kind("while x less 10 and y :<\n>:").unwrap(),
+        Kind::while_block(
+            Exp::operator(
+                Exp::operator(Exp::variable("x"), Op::LessThan, 10),
+                Op::And,
+                Exp::variable("y")
+            ),
+            vec![]
+        )
+    );
+}
+
+#[test]
+fn test_keyword_operators_need_word_boundaries() {
+    assert!(grammar::expression("a orb").is_err());
+    assert!(grammar::expression("1 andy").is_err());
+    assert!(grammar::expression("x moreb").is_err());
+    assert!(grammar::expression("a\tor b").is_ok());
+
+    assert_eq!(
+        grammar::statement_kind("x squanch a or bandit").unwrap(),
+        Kind::assignment(
+            "x",
+            Exp::operator(Exp::variable("a"), Op::Or, Exp::variable("bandit"))
+        )
+    );
+}
+
 #[test]
 fn test_eval_static_string() {
     let l = grammar::expression(r#"{"(1 + 3)"}"#).unwrap();
@@ -303,6 +432,29 @@ fn test_catch() {
     assert_eq!(l, Kind::catch(vec![x], vec![error]));
 }
 
+#[test]
+fn test_catch_binding_and_rethrow() {
+    let l = grammar::statement_kind(
+        r#"normal plan :<
+        x squanch nope
+    >: plan for failure e :<
+        rethrow e
+    >:"#,
+    )
+    .unwrap();
+
+    let x = statement(Kind::assignment("x", Exp::variable("nope")));
+    let rethrow = statement(Kind::rethrow(Exp::variable("e")));
+
+    assert_eq!(l, Kind::catch_as(vec![x], "e", vec![rethrow]));
+}
+
+#[test]
+fn test_throw() {
+    let l = grammar::statement_kind("throw (1 + 2)").unwrap();
+    assert_eq!(l, Kind::throw(Exp::operator(1, Op::Add, 2)));
+}
+
 #[test]
 fn test_args() {
     let l = grammar::args(r#"(x, "bar")"#).unwrap();
@@ -310,6 +462,46 @@ fn test_args() {
     assert_eq!(l, vec![Exp::variable("x"), Exp::value("bar")]);
 }
 
+#[test]
+fn test_call_any_expression() {
+    let l = grammar::expression("makeAdder(1)(2)").unwrap();
+    let make_adder = Exp::from(ExpKind::FunctionCall("makeAdder".into(), vec![Exp::new(1)]));
+    assert_eq!(l, Exp::call(make_adder, vec![Exp::new(2)]));
+
+    let l = grammar::statement_kind("handlers[0](x)").unwrap();
+    assert_eq!(
+        l,
+        Kind::Call(Exp::call(
+            Exp::list_index("handlers", 0),
+            vec![Exp::variable("x")]
+        ))
+    );
+}
+
+#[test]
+fn test_lambda() {
+    let l = grammar::expression("meeseeks (x) -> (x * 2)").unwrap();
+    let double = Exp::operator(Exp::variable("x"), Op::Multiply, 2);
+    let body = vec![statement(Kind::return_it(double))];
+    assert_eq!(l, Exp::from(ExpKind::Lambda(vec!["x".into()], body)));
+
+    let l = grammar::expression(
+        r#"meeseeks(a, b) :<
+        show me what you got a
+        return b
+    >:"#,
+    )
+    .unwrap();
+    let body = vec![
+        statement(Kind::print(Exp::variable("a"))),
+        statement(Kind::return_it(Exp::variable("b"))),
+    ];
+    assert_eq!(
+        l,
+        Exp::from(ExpKind::Lambda(vec!["a".into(), "b".into()], body))
+    );
+}
+
 #[test]
 fn test_function_def() {
     let l = grammar::statement_kind(
@@ -320,9 +512,9 @@ fn test_function_def() {
     .unwrap();
 
     let addition = Exp::operator(Exp::variable("x"), Op::Add, Exp::variable("y"));
-    let print = vec![Statement::new(Kind::print(addition), 0, 1)];
+    let print = vec![statement(Kind::print(addition))];
 
-    let func = Statement::new(Kind::function("foo", vec!["x", "y"], print), 0, 1);
+    let func = statement(Kind::function("foo", vec!["x", "y"], print));
 
     assert_eq!(func, l);
 }
@@ -337,9 +529,9 @@ fn test_function_no_space_in_name_and_params() {
     .unwrap();
 
     let addition = Exp::operator(Exp::variable("x"), Op::Add, Exp::variable("y"));
-    let print = vec![Statement::new(Kind::print(addition), 0, 1)];
+    let print = vec![statement(Kind::print(addition))];
 
-    let func = Statement::new(Kind::function("foo", vec!["x", "y"], print), 0, 1);
+    let func = statement(Kind::function("foo", vec!["x", "y"], print));
 
     assert_eq!(func, l);
 }
@@ -353,13 +545,31 @@ fn test_function_no_spaces_in_def() {
     )
     .unwrap();
 
-    let print = vec![Statement::new(Kind::print(Exp::variable("x")), 0, 1)];
+    let print = vec![statement(Kind::print(Exp::variable("x")))];
 
-    let func = Statement::new(Kind::function("foo", vec!["x"], print), 0, 1);
+    let func = statement(Kind::function("foo", vec!["x"], print));
 
     assert_eq!(func, l[0]);
 }
 
+#[test]
+fn test_global() {
+    let l = grammar::statement_kind("global x, y").unwrap();
+    assert_eq!(l, Kind::global(vec!["x", "y"]));
+}
+
+#[test]
+fn test_nonlocal() {
+    let l = grammar::statement_kind("nonlocal count").unwrap();
+    assert_eq!(l, Kind::nonlocal(vec!["count"]));
+}
+
+#[test]
+fn test_variable_named_global() {
+    let l = grammar::statement_kind("global squanch 10").unwrap();
+    assert_eq!(l, Kind::assignment("global", 10));
+}
+
 #[test]
 fn test_modulus() {
     let l = grammar::expression(r#"(5 % 4)"#).unwrap();
@@ -395,3 +605,175 @@ fn test_works_on_windows_fuck_windows() {
     assert_eq!(l[0], Statement::tnew(Kind::assignment("x", 100)));
     assert_eq!(l[1], Statement::tnew(Kind::print(Exp::variable("x"))));
 }
+
+#[test]
+fn test_spans() {
+    let source = "x squanch 100\n  y squanch (x + \"é\")";
+    let file = Files::default().add("spans.y", source);
+    let l = grammar::parse(source, file).unwrap();
+
+    let span = l[1].span();
+    assert_eq!(
+        (span.file, span.line, span.column, span.len),
+        (file, 2, 3, 20)
+    );
+
+    match l[1].kind {
+        Kind::Assignment(_, ref exp) => match exp.kind {
+            ExpKind::OpExp(_, _, ref right) => {
+                assert_eq!(**right, Exp::new("é"));
+                let span = right.span();
+                assert_eq!((span.line, span.column, span.len), (2, 18, 4));
+            }
+            ref kind => panic!("expected an operator expression, got {:?}", kind),
+        },
+        ref kind => panic!("expected an assignment, got {:?}", kind),
+    }
+}
+
+#[test]
+fn test_import() {
+    assert_eq!(
+        grammar::statement_kind(r#"interdimensional cable "lib/shapes.y""#).unwrap(),
+        Kind::import("lib/shapes.y", Imports::Module(None))
+    );
+
+    assert_eq!(
+        grammar::statement_kind(r#"interdimensional cable "lib/shapes.y" as s"#).unwrap(),
+        Kind::import("lib/shapes.y", Imports::Module(Some("s".into())))
+    );
+
+    assert_eq!(
+        grammar::statement_kind(r#"interdimensional cable "shapes.y" showing square, sides"#)
+            .unwrap(),
+        Kind::import(
+            "shapes.y",
+            Imports::Names(vec!["square".into(), "sides".into()])
+        )
+    );
+
+    assert!(grammar::statement_kind(r#"interdimensional cable "shapes.y" showing"#).is_err());
+}
+
+#[test]
+fn test_chained_indexing() {
+    assert_eq!(
+        grammar::expression("m[i][j + 1]").unwrap(),
+        Exp::index(
+            Exp::list_index("m", Exp::variable("i")),
+            Exp::operator(Exp::variable("j"), Op::Add, 1)
+        )
+    );
+
+    assert_eq!(
+        grammar::expression("f(x)[0](1)").unwrap(),
+        Exp::call(
+            Exp::index(
+                ExpKind::FunctionCall("f".into(), vec![Exp::variable("x")]),
+                0
+            ),
+            vec![1.into()]
+        )
+    );
+
+    assert_eq!(
+        grammar::statement_kind("m[i][0] squanch 1").unwrap(),
+        Kind::ListAssign("m".into(), vec![Exp::variable("i"), 0.into()], 1.into())
+    );
+
+    assert_eq!(
+        grammar::statement_kind(r#"squanch m["rick"][2]"#).unwrap(),
+        Kind::ListDelete("m".into(), vec!["rick".into(), 2.into()])
+    );
+}
+
+#[test]
+fn test_list_literals() {
+    assert_eq!(grammar::expression("[]").unwrap(), Exp::list(vec![]));
+
+    assert_eq!(
+        grammar::expression(r#"[1, "two", rick, [x, -4]]"#).unwrap(),
+        Exp::list(vec![
+            1.into(),
+            "two".into(),
+            true.into(),
+            Exp::list(vec![Exp::variable("x"), (-4).into()]),
+        ])
+    );
+
+    assert_eq!(
+        grammar::expression("[\n    1 + 2,\n    f(x),\n]").unwrap(),
+        Exp::list(vec![
+            Exp::operator(1, Op::Add, 2),
+            ExpKind::FunctionCall("f".into(), vec![Exp::variable("x")]).into(),
+        ])
+    );
+
+    assert_eq!(
+        grammar::expression("[1, 2][0]").unwrap(),
+        Exp::index(Exp::list(vec![1.into(), 2.into()]), 0)
+    );
+
+    assert!(grammar::expression("[,]").is_err());
+    assert!(grammar::expression("[1,, 2]").is_err());
+}
+
+#[test]
+fn test_for() {
+    assert_eq!(
+        grammar::statement_kind("for x in l :<\n    show me what you got x\n>:").unwrap(),
+        Kind::for_each(
+            "x",
+            Exp::variable("l"),
+            vec![statement(Kind::print(Exp::variable("x")))]
+        )
+    );
+
+    assert_eq!(
+        grammar::statement_kind(r#"for c in upper("rick") :< >:"#).unwrap(),
+        Kind::for_each(
+            "c",
+            ExpKind::FunctionCall("upper".into(), vec!["rick".into()]),
+            vec![]
+        )
+    );
+
+    assert_eq!(
+        grammar::statement_kind("for i in 0 up to n * 2 :< >:").unwrap(),
+        Kind::for_range(
+            "i",
+            0,
+            Exp::operator(Exp::variable("n"), Op::Multiply, 2),
+            vec![]
+        )
+    );
+
+    assert!(grammar::statement_kind("for i in :< >:").is_err());
+}
+
+#[test]
+fn test_break_continue() {
+    assert_eq!(grammar::statement_kind("break").unwrap(), Kind::Break(None));
+    assert_eq!(
+        grammar::statement_kind("continue outer").unwrap(),
+        Kind::Continue(Some("outer".into()))
+    );
+
+    assert_eq!(
+        grammar::statement_kind("outer: while rick :<\n    break outer\n>:").unwrap(),
+        Kind::Labeled(
+            "outer".into(),
+            Box::new(statement(Kind::while_block(
+                true,
+                vec![statement(Kind::Break(Some("outer".into())))]
+            )))
+        )
+    );
+
+    assert_eq!(
+        grammar::statement_kind("breakfast squanch 1").unwrap(),
+        Kind::assignment("breakfast", 1)
+    );
+
+    assert!(grammar::statement_kind("outer: show me what you got 1").is_err());
+}
@@ -0,0 +1,111 @@
+use std::{
+    cell::RefCell,
+    io::{self, prelude::*},
+    rc::Rc,
+};
+
+pub trait Io {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize>;
+
+    fn stdout(&mut self) -> &mut dyn Write;
+
+    fn stderr(&mut self) -> &mut dyn Write;
+}
+
+pub struct StdIo {
+    stdout: io::Stdout,
+    stderr: io::Stderr,
+}
+
+impl StdIo {
+    pub fn new() -> Self {
+        Self {
+            stdout: io::stdout(),
+            stderr: io::stderr(),
+        }
+    }
+}
+
+impl Default for StdIo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Io for StdIo {
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        io::stdin().read_line(buf)
+    }
+
+    fn stdout(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+
+    fn stderr(&mut self) -> &mut dyn Write {
+        &mut self.stderr
+    }
+}
+
+pub struct Streams<I, O, E> {
+    pub stdin: I,
+    pub stdout: O,
+    pub stderr: E,
+}
+
+impl<I, O, E> Streams<I, O, E> {
+    pub fn new(stdin: I, stdout: O, stderr: E) -> Self {
+        Self {
+            stdin,
+            stdout,
+            stderr,
+        }
+    }
+}
+
+impl<I, O, E> Io for Streams<I, O, E>
+where
+    I: BufRead,
+    O: Write,
+    E: Write,
+{
+    fn read_line(&mut self, buf: &mut String) -> io::Result<usize> {
+        self.stdin.read_line(buf)
+    }
+
+    fn stdout(&mut self) -> &mut dyn Write {
+        &mut self.stdout
+    }
+
+    fn stderr(&mut self) -> &mut dyn Write {
+        &mut self.stderr
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer {
+    buf: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buf.borrow()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.buf.borrow_mut().clear();
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.borrow_mut().write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
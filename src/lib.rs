@@ -1,5 +1,10 @@
-use std::{fs::File, io::prelude::*};
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+};
 
+pub mod builtins;
 pub mod grammar;
 
 #[cfg(test)]
@@ -7,37 +12,45 @@ mod grammar_tests;
 
 pub mod error;
 pub mod expression;
+pub mod io;
+mod module;
+pub mod plugin;
+pub mod repl;
+pub mod span;
 pub mod state;
 pub mod statement;
+pub mod symbols;
 mod util;
 pub mod value;
-mod vec_map;
-
-use crate::{state::*, statement::*};
-
-const BUILTINS_FILE: &str = "builtins.y";
-const BUILTINS: &str = include_str!("builtins.y");
+pub mod vm;
+
+use crate::{
+    error::{EitherError, RunError},
+    expression::Expression,
+    io::Io,
+    span::FileId,
+    state::*,
+    statement::*,
+    value::Value,
+};
 
 #[macro_export]
 macro_rules! plugin_fn {
     ($internal_name:ident, $external_name:ident) => {
         #[no_mangle]
-        pub unsafe extern "C" fn $external_name(args: *mut Vec<Value>) -> *mut SwResult<Value> {
-            let args_ref: &mut Vec<Value> = args
-                .as_mut()
-                .expect("args given to schwift extern fn should never be null");
-
-            let result: SwResult<Value> = $internal_name(args_ref);
-
-            Box::into_raw(Box::new(result))
+        pub unsafe extern "C" fn $external_name(
+            args: *const $crate::plugin::SwValue,
+            len: usize,
+        ) -> $crate::plugin::SwReturn {
+            $crate::plugin::export(args, len, $internal_name)
         }
     };
 }
 
 #[no_mangle]
-pub static LIBSCHWIFT_ABI_COMPAT: u32 = 2;
+pub static LIBSCHWIFT_ABI_COMPAT: u32 = plugin::ABI_VERSION;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Operator {
     Add,
     Subtract,
@@ -55,92 +68,142 @@ pub enum Operator {
     Modulus,
 }
 
-fn get_line<'a>(file: &'a str, err: &grammar::ParseError) -> &'a str {
-    let mut count = 0;
-    let mut last_newline = 0usize;
+pub fn compile(filename: &str) -> Result<Vec<Statement>, RunError> {
+    let source = fs::read_to_string(filename).map_err(|error| RunError::Io {
+        file: filename.into(),
+        error,
+    })?;
 
-    for i in 0..file.len() {
-        if file.is_char_boundary(i) {
-            let symbol = &file[i..=i];
+    grammar::parse(&source, FileId::default()).map_err(|e| RunError::syntax(filename, &source, e))
+}
 
-            if symbol == "\n" {
-                count += 1;
+pub struct Interpreter {
+    state: State,
+}
 
-                if count == err.location.line {
-                    return &file[last_newline..i];
-                }
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_state(State::new())
+    }
 
-                last_newline = i + 1;
-            }
-        }
+    pub fn with_io<I>(io: I) -> Self
+    where
+        I: Io + 'static,
+    {
+        Self::with_state(State::with_io(io))
     }
 
-    panic!(
-        "Got grammar error with invalid line number {}",
-        err.location.line
-    );
-}
+    fn with_state(state: State) -> Self {
+        Self { state }
+    }
 
-fn place_carat(err: &grammar::ParseError) -> String {
-    let mut s = String::new();
+    pub fn set_args(&mut self, args: &[&str]) {
+        self.state.parse_args(args);
+    }
 
-    for _ in 0..err.location.column - 1 {
-        s.push(' ');
+    pub fn use_vm(&mut self, enabled: bool) {
+        self.state.use_vm(enabled);
     }
 
-    s.push('^');
+    pub fn allow_legacy_abi(&mut self, enabled: bool) {
+        self.state.allow_legacy_abi(enabled);
+    }
 
-    s
-}
+    pub fn add_search_path<P>(&mut self, dir: P)
+    where
+        P: Into<PathBuf>,
+    {
+        self.state.add_search_path(dir);
+    }
 
-pub fn compile(filename: &str) -> Vec<Statement> {
-    let mut f = match File::open(filename) {
-        Result::Ok(i) => i,
-        Result::Err(_) => panic!("Failed to open file {}", filename),
-    };
-    let mut s = String::new();
-    match f.read_to_string(&mut s) {
-        Result::Ok(_) => {}
-        Result::Err(_) => panic!("Failed to read file {}", filename),
-    };
+    pub fn state(&self) -> &State {
+        &self.state
+    }
 
-    parse_str(&s, filename)
-}
+    pub fn state_mut(&mut self) -> &mut State {
+        &mut self.state
+    }
 
-fn parse_str(source: &str, filename: &str) -> Vec<Statement> {
-    match grammar::file(source) {
-        Ok(statements) => statements,
-        Err(ref e) => {
-            println!(
-                "SYNTAX ERROR: {}:{}\n{}\n{}",
-                filename,
-                e.location.line,
-                get_line(source, e),
-                place_carat(e)
-            );
-            std::process::exit(1);
-        }
+    pub fn run_str(&mut self, source: &str, name: &str) -> Result<(), RunError> {
+        // Everything run is kept, so errors raised by code from an earlier run
+        // can still show where that code was written.
+        let file = self.state.add_file(name, source);
+        let tokens = grammar::parse(source, file).map_err(|e| RunError::syntax(name, source, e))?;
+
+        let result = self.state.run(&tokens);
+        // A `return` at the top level ends this run, not the ones after it.
+        self.state.take_return();
+
+        result.map_err(|error| self.runtime_error(file, error.into()))
     }
-}
 
-pub fn run_program(filename: &str, args: &[&str]) {
-    let mut s = State::new();
+    pub fn run_file<P>(&mut self, path: P) -> Result<(), RunError>
+    where
+        P: AsRef<Path>,
+    {
+        let name = path.as_ref().display().to_string();
+        let source = fs::read_to_string(&path).map_err(|error| RunError::Io {
+            file: name.clone(),
+            error,
+        })?;
+
+        self.run_str(&source, &name)
+    }
 
-    s.parse_args(args);
+    pub fn eval_str(&mut self, source: &str, name: &str) -> Result<Value, RunError> {
+        let (file, expression) = self.parse_expression(source, name)?;
 
-    let tokens = parse_str(BUILTINS, BUILTINS_FILE);
+        expression
+            .evaluate(&self.state)
+            .map(Cow::into_owned)
+            .map_err(|error| self.runtime_error(file, error))
+    }
 
-    match s.run(&tokens) {
-        Ok(()) => {}
-        Err(e) => e.panic(BUILTINS_FILE),
+    /// Like `eval_str`, but calling a function that doesn't return anything
+    /// gives `None` instead of failing, so functions that only print can be
+    /// called from the REPL.
+    pub fn eval_or_call_str(
+        &mut self,
+        source: &str,
+        name: &str,
+    ) -> Result<Option<Value>, RunError> {
+        let (file, expression) = self.parse_expression(source, name)?;
+
+        self.state
+            .evaluate_call(&expression)
+            .map_err(|error| self.runtime_error(file, error))
     }
 
-    let tokens = compile(filename);
+    fn parse_expression(
+        &mut self,
+        source: &str,
+        name: &str,
+    ) -> Result<(FileId, Expression), RunError> {
+        let file = self.state.add_file(name, source);
+        let expression = grammar::parse_expression(source, file)
+            .map_err(|e| RunError::syntax(name, source, e))?;
 
-    match s.run(&tokens) {
-        Ok(()) => {}
-        Err(e) => e.panic(filename),
+        Ok((file, expression))
     }
 
-    std::mem::forget(s);
+    fn runtime_error(&self, file: FileId, error: EitherError) -> RunError {
+        RunError::runtime(&self.state.files(), file, error)
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn run_program(filename: &str, args: &[&str]) -> Result<(), RunError> {
+    let mut interpreter = Interpreter::new();
+
+    interpreter.set_args(args);
+    interpreter.run_file(filename)?;
+
+    std::mem::forget(interpreter);
+
+    Ok(())
 }
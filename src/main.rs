@@ -1,4 +1,6 @@
 use clap::{App, AppSettings, Arg};
+use schwift::Interpreter;
+use std::{env, process};
 
 fn main() {
     let matches = App::new("The Schwift interpreter")
@@ -12,10 +14,21 @@ fn main() {
         .arg(
             Arg::with_name("SOURCE")
                 .value_name("FILE")
-                .help("The schwift source file that you want to interpret")
-                .takes_value(true)
-                .required(true),
+                .help(
+                    "The schwift source file that you want to interpret. Leave it out (or pass \
+                     `repl`) to start an interactive session",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("vm")
+                .long("vm")
+                .help("Run on the experimental bytecode VM instead of the tree walker"),
         )
+        .arg(Arg::with_name("legacy-abi").long("legacy-abi").help(
+            "Load ABI 2 microverses. Only safe if they were built with the same rustc and \
+             schwift version as this interpreter",
+        ))
         .arg(
             Arg::with_name("args")
                 .help("Args to pass to the program")
@@ -29,5 +42,26 @@ fn main() {
         None => Vec::new(),
     };
 
-    schwift::run_program(matches.value_of("SOURCE").unwrap(), &args);
+    let mut interpreter = Interpreter::new();
+    interpreter.set_args(&args);
+    interpreter.use_vm(matches.is_present("vm"));
+    interpreter.allow_legacy_abi(matches.is_present("legacy-abi"));
+
+    if let Some(paths) = env::var_os("SCHWIFT_PATH") {
+        for dir in env::split_paths(&paths) {
+            interpreter.add_search_path(dir);
+        }
+    }
+
+    match matches.value_of("SOURCE") {
+        None | Some("repl") => schwift::repl::run(interpreter),
+        Some(source) => {
+            if let Err(e) = interpreter.run_file(source) {
+                eprintln!("{}", e.report());
+                process::exit(1);
+            }
+
+            std::mem::forget(interpreter);
+        }
+    }
 }
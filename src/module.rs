@@ -0,0 +1,72 @@
+use crate::{
+    error::{ErrorKind, SwResult},
+    value::Value,
+};
+use std::{
+    collections::HashMap,
+    iter,
+    path::{Path, PathBuf},
+};
+
+/// Every `.y` file imported so far. A program and all of its modules share one
+/// of these, so each file only runs once.
+#[derive(Default)]
+pub(crate) struct Modules {
+    search_path: Vec<PathBuf>,
+    loaded: HashMap<PathBuf, Value>,
+    loading: Vec<(PathBuf, String)>,
+    libraries: Vec<libloading::Library>,
+}
+
+impl Modules {
+    pub fn add_search_path(&mut self, dir: PathBuf) {
+        self.search_path.push(dir);
+    }
+
+    /// Finds `path` next to the importing file, then in each search path
+    /// directory in order.
+    pub fn resolve(&self, path: &str, importer: &Path) -> SwResult<PathBuf> {
+        let dir = importer.parent().unwrap_or_else(|| Path::new(""));
+
+        iter::once(dir)
+            .chain(self.search_path.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(path))
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| ErrorKind::ModuleNotFound(path.into()).into())
+    }
+
+    pub fn get(&self, key: &Path) -> Option<Value> {
+        self.loaded.get(key).cloned()
+    }
+
+    /// Marks `key` as running, failing if it is already partway through.
+    pub fn enter(&mut self, key: PathBuf, name: String) -> SwResult<()> {
+        if let Some(start) = self.loading.iter().position(|(k, _)| *k == key) {
+            let cycle = self.loading[start..]
+                .iter()
+                .map(|(_, name)| name.as_str())
+                .chain(iter::once(name.as_str()))
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            return Err(ErrorKind::ImportCycle(cycle).into());
+        }
+
+        self.loading.push((key, name));
+        Ok(())
+    }
+
+    pub fn leave(&mut self) {
+        self.loading.pop();
+    }
+
+    pub fn finish(&mut self, key: PathBuf, module: Value) {
+        self.loaded.insert(key, module);
+    }
+
+    /// Keeps microverses loaded by a module open for as long as its functions
+    /// can be called.
+    pub fn keep(&mut self, libraries: &mut Vec<libloading::Library>) {
+        self.libraries.append(libraries);
+    }
+}
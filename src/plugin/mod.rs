@@ -0,0 +1,352 @@
+//! The C ABI spoken between schwift and its microverses.
+//!
+//! Values cross the boundary as `SwValue`s. Arguments are owned by the caller
+//! and only borrowed for the duration of the call. A returned value is owned by
+//! the plugin, so schwift copies it and hands it back to the plugin's exported
+//! `schwift_free_value` to be released with the allocator that created it.
+
+use crate::{
+    error::{ErrorKind, SwResult},
+    value::{Key, Map, Value},
+};
+use std::{ptr, slice, str};
+
+#[cfg(test)]
+mod test;
+
+/// The `LIBSCHWIFT_ABI_COMPAT` value of plugins using this module's ABI.
+pub const ABI_VERSION: u32 = 3;
+
+/// The `LIBSCHWIFT_ABI_COMPAT` value of plugins that pass `Vec<Value>`s around.
+/// Their layout can't be checked, so they only load when that's switched on.
+pub const LEGACY_ABI_VERSION: u32 = 2;
+
+pub const FREE_SYMBOL: &[u8] = b"schwift_free_value";
+
+pub type SwFn = unsafe extern "C" fn(args: *const SwValue, len: usize) -> SwReturn;
+pub type SwFreeFn = unsafe extern "C" fn(value: SwValue);
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwTag(pub u32);
+
+impl SwTag {
+    pub const INT: Self = SwTag(0);
+    pub const FLOAT: Self = SwTag(1);
+    pub const BOOL: Self = SwTag(2);
+    pub const STR: Self = SwTag(3);
+    pub const LIST: Self = SwTag(4);
+    pub const MAP: Self = SwTag(5);
+}
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwStatus(pub u32);
+
+impl SwStatus {
+    pub const OK: Self = SwStatus(0);
+    /// The returned value is a string describing what went wrong.
+    pub const ERROR: Self = SwStatus(1);
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SwStr {
+    pub ptr: *mut u8,
+    pub len: usize,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SwList {
+    pub ptr: *mut SwValue,
+    pub len: usize,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SwMap {
+    pub ptr: *mut SwEntry,
+    pub len: usize,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union SwData {
+    pub int: i64,
+    pub float: f64,
+    pub boolean: u8,
+    pub str: SwStr,
+    pub list: SwList,
+    pub map: SwMap,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SwValue {
+    pub tag: SwTag,
+    pub data: SwData,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SwEntry {
+    pub key: SwValue,
+    pub value: SwValue,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct SwReturn {
+    pub status: SwStatus,
+    pub value: SwValue,
+}
+
+impl SwValue {
+    /// Copies `value` into memory owned by the current allocator. It has to be
+    /// released with `free`.
+    pub fn new(value: &Value) -> SwResult<Self> {
+        let (tag, data) = match *value {
+            Value::Int(int) => (SwTag::INT, SwData { int }),
+            Value::Float(float) => (SwTag::FLOAT, SwData { float }),
+            Value::Bool(b) => (SwTag::BOOL, SwData { boolean: b as u8 }),
+            Value::Str(ref s) => return Ok(Self::string(s)),
+            Value::List(ref list) => {
+                let items = Owned::new(list.iter().map(Self::new))?.into_boxed_slice();
+                let len = items.len();
+                let ptr = Box::into_raw(items) as *mut SwValue;
+
+                (
+                    SwTag::LIST,
+                    SwData {
+                        list: SwList { ptr, len },
+                    },
+                )
+            }
+            Value::Map(ref map) => {
+                let mut keys = Vec::with_capacity(map.len());
+                let mut values = Vec::with_capacity(map.len());
+                for (key, value) in map {
+                    keys.push(match *key {
+                        Key::Int(int) => Self::from(int),
+                        Key::Str(ref s) => Self::string(s),
+                    });
+                    values.push(value);
+                }
+
+                let keys = Owned(keys);
+                let values = Owned::new(values.into_iter().map(Self::new))?;
+
+                let entries: Box<[SwEntry]> = keys
+                    .into_vec()
+                    .into_iter()
+                    .zip(values.into_vec())
+                    .map(|(key, value)| SwEntry { key, value })
+                    .collect();
+                let len = entries.len();
+                let ptr = Box::into_raw(entries) as *mut SwEntry;
+
+                (
+                    SwTag::MAP,
+                    SwData {
+                        map: SwMap { ptr, len },
+                    },
+                )
+            }
+            _ => return Err(ErrorKind::UnportableValue(value.get_type()).into()),
+        };
+
+        Ok(Self { tag, data })
+    }
+
+    pub fn string(s: &str) -> Self {
+        let bytes = s.as_bytes().to_vec().into_boxed_slice();
+        let len = bytes.len();
+        let ptr = Box::into_raw(bytes) as *mut u8;
+
+        Self {
+            tag: SwTag::STR,
+            data: SwData {
+                str: SwStr { ptr, len },
+            },
+        }
+    }
+
+    /// Copies the value back into a schwift `Value`.
+    ///
+    /// # Safety
+    ///
+    /// The pointers inside the value have to be valid for reads.
+    pub unsafe fn to_value(&self) -> SwResult<Value> {
+        match self.tag {
+            SwTag::INT => Ok(Value::Int(self.data.int)),
+            SwTag::FLOAT => Ok(Value::Float(self.data.float)),
+            SwTag::BOOL => Ok(Value::Bool(self.data.boolean != 0)),
+            SwTag::STR => {
+                let SwStr { ptr, len } = self.data.str;
+                match str::from_utf8(parts(ptr, len)) {
+                    Ok(s) => Ok(Value::Str(s.to_string())),
+                    Err(e) => Err(ErrorKind::CorruptPluginValue(e.to_string()).into()),
+                }
+            }
+            SwTag::LIST => {
+                let SwList { ptr, len } = self.data.list;
+                parts(ptr, len)
+                    .iter()
+                    .map(|item| item.to_value())
+                    .collect::<SwResult<_>>()
+                    .map(Value::List)
+            }
+            SwTag::MAP => {
+                let SwMap { ptr, len } = self.data.map;
+                let mut map = Map::new();
+                for entry in parts(ptr, len) {
+                    let key = Key::from_value(&entry.key.to_value()?)?;
+                    map.insert(key, entry.value.to_value()?);
+                }
+
+                Ok(Value::Map(map))
+            }
+            SwTag(tag) => Err(ErrorKind::CorruptPluginValue(format!("unknown tag {}", tag)).into()),
+        }
+    }
+
+    /// Releases a value created by `new`.
+    ///
+    /// # Safety
+    ///
+    /// The value has to come from `SwValue::new` in this copy of schwift and
+    /// can't be used afterwards.
+    pub unsafe fn free(self) {
+        match self.tag {
+            SwTag::STR => {
+                let SwStr { ptr, len } = self.data.str;
+                drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)));
+            }
+            SwTag::LIST => {
+                let SwList { ptr, len } = self.data.list;
+                let items = Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len));
+                drop(Owned(items.into_vec()));
+            }
+            SwTag::MAP => {
+                let SwMap { ptr, len } = self.data.map;
+                for entry in Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)).into_vec() {
+                    entry.key.free();
+                    entry.value.free();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl From<i64> for SwValue {
+    fn from(int: i64) -> Self {
+        Self {
+            tag: SwTag::INT,
+            data: SwData { int },
+        }
+    }
+}
+
+unsafe fn parts<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len)
+    }
+}
+
+/// `SwValue`s created by this copy of schwift, freed when dropped.
+struct Owned(Vec<SwValue>);
+
+impl Owned {
+    fn new<I>(values: I) -> SwResult<Self>
+    where
+        I: Iterator<Item = SwResult<SwValue>>,
+    {
+        let mut owned = Owned(Vec::new());
+        for value in values {
+            owned.0.push(value?);
+        }
+
+        Ok(owned)
+    }
+
+    fn into_vec(mut self) -> Vec<SwValue> {
+        std::mem::take(&mut self.0)
+    }
+
+    fn into_boxed_slice(self) -> Box<[SwValue]> {
+        self.into_vec().into_boxed_slice()
+    }
+}
+
+impl Drop for Owned {
+    fn drop(&mut self) {
+        for value in self.0.drain(..) {
+            unsafe { value.free() }
+        }
+    }
+}
+
+/// Calls a plugin function from the host side.
+///
+/// # Safety
+///
+/// `f` and `free` have to follow the ABI described in this module.
+pub unsafe fn call(f: SwFn, free: SwFreeFn, args: &[Value]) -> SwResult<Value> {
+    let args = Owned::new(args.iter().map(SwValue::new))?;
+
+    let ret = f(args.0.as_ptr(), args.0.len());
+    drop(args);
+
+    let value = ret.value.to_value();
+    free(ret.value);
+
+    match ret.status {
+        SwStatus::OK => value,
+        SwStatus::ERROR => Err(ErrorKind::PluginError(value?.to_string()).into()),
+        SwStatus(status) => {
+            Err(ErrorKind::CorruptPluginValue(format!("unknown status {}", status)).into())
+        }
+    }
+}
+
+/// Runs a Rust plugin function on the plugin side. Used by `plugin_fn!`.
+///
+/// # Safety
+///
+/// `args` has to point to `len` valid values.
+pub unsafe fn export<F>(args: *const SwValue, len: usize, f: F) -> SwReturn
+where
+    F: FnOnce(&mut Vec<Value>) -> SwResult<Value>,
+{
+    let result = parts(args, len)
+        .iter()
+        .map(|arg| arg.to_value())
+        .collect::<SwResult<Vec<_>>>()
+        .and_then(|mut args| f(&mut args))
+        .and_then(|value| SwValue::new(&value));
+
+    match result {
+        Ok(value) => SwReturn {
+            status: SwStatus::OK,
+            value,
+        },
+        Err(error) => SwReturn {
+            status: SwStatus::ERROR,
+            value: SwValue::string(&error.kind().to_string()),
+        },
+    }
+}
+
+/// Frees values returned by the `plugin_fn!`s of this plugin.
+///
+/// # Safety
+///
+/// Only called by schwift on values returned from this plugin.
+#[no_mangle]
+pub unsafe extern "C" fn schwift_free_value(value: SwValue) {
+    value.free()
+}
@@ -0,0 +1,95 @@
+use super::*;
+use crate::{
+    error::{ErrorKind as EKind, SwResult},
+    grammar, plugin_fn,
+    value::{Type, Value},
+};
+
+plugin_fn!(double_internal, test_plugin_double);
+plugin_fn!(fail_internal, test_plugin_fail);
+
+// `plugin_fn!` hands plugins a `Vec`.
+#[allow(clippy::ptr_arg)]
+fn double_internal(args: &mut Vec<Value>) -> SwResult<Value> {
+    args[0].multiply(&Value::new(2))
+}
+
+#[allow(clippy::ptr_arg)]
+fn fail_internal(args: &mut Vec<Value>) -> SwResult<Value> {
+    Err(EKind::IndexOutOfBounds {
+        len: args.len(),
+        index: 3,
+    }
+    .into())
+}
+
+fn round_trip(value: &Value) -> SwResult<Value> {
+    let sw = SwValue::new(value)?;
+    let back = unsafe { sw.to_value() };
+    unsafe { sw.free() };
+    back
+}
+
+#[test]
+fn values_round_trip() {
+    let values = ["137", "1.5", "rick", r#""wubba lubba dub dub""#, r#""""#];
+
+    for source in &values {
+        let value = grammar::value(source).unwrap();
+        assert_eq!(round_trip(&value).unwrap(), value);
+    }
+
+    let mut map = Map::new();
+    map.insert(Key::Str("portal".into()), Value::new(vec![1, 2]));
+    map.insert(Key::Int(42), Value::new("gun"));
+    let nested = Value::new(vec![Value::Map(map), Value::List(vec![]), Value::new(true)]);
+
+    assert_eq!(round_trip(&nested).unwrap(), nested);
+}
+
+#[test]
+fn functions_are_not_portable() {
+    let mut state = crate::state::State::new();
+    state
+        .run(&grammar::file("f() :<\n return 1\n>:").unwrap())
+        .unwrap();
+
+    let function = state.get("f").unwrap().clone();
+    let err = round_trip(&Value::new(vec![function])).unwrap_err();
+    assert_eq!(*err.kind(), EKind::UnportableValue(Type::Function));
+}
+
+#[test]
+fn unknown_tags_are_rejected() {
+    let bogus = SwValue {
+        tag: SwTag(99),
+        data: SwData { int: 0 },
+    };
+
+    let err = unsafe { bogus.to_value() }.unwrap_err();
+    assert_eq!(
+        *err.kind(),
+        EKind::CorruptPluginValue("unknown tag 99".into())
+    );
+}
+
+#[test]
+fn calls_plugin_functions() {
+    let result = unsafe { call(test_plugin_double, schwift_free_value, &[Value::new(21)]) };
+    assert_eq!(result.unwrap(), Value::new(42));
+
+    let result = unsafe { call(test_plugin_double, schwift_free_value, &[Value::new(true)]) };
+    let err = result.unwrap_err();
+    match *err.kind() {
+        EKind::PluginError(ref message) => assert!(message.contains("apples and space worms")),
+        ref kind => panic!("expected a plugin error, got {:?}", kind),
+    }
+}
+
+#[test]
+fn plugin_errors_keep_their_message() {
+    let result = unsafe { call(test_plugin_fail, schwift_free_value, &[]) };
+    let expected = EKind::IndexOutOfBounds { len: 0, index: 3 }.to_string();
+
+    assert_eq!(*result.unwrap_err().kind(), EKind::PluginError(expected));
+}
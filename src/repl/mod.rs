@@ -0,0 +1,104 @@
+use crate::{grammar, Interpreter};
+use std::mem;
+
+#[cfg(test)]
+mod test;
+
+const PROMPT: &str = ">>> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+pub fn run(mut interpreter: Interpreter) {
+    let mut buffer = String::new();
+    let mut inputs = 0;
+
+    loop {
+        let prompt = if buffer.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+
+        let mut line = String::new();
+        let read = {
+            let mut io = interpreter.state().io();
+            write!(io.stdout(), "{}", prompt)
+                .and_then(|_| io.stdout().flush())
+                .and_then(|_| io.read_line(&mut line))
+        };
+
+        match read {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                let _ = writeln!(interpreter.state().io().stderr(), "{}", e);
+                break;
+            }
+        }
+
+        buffer.push_str(&line);
+
+        if open_blocks(&buffer) > 0 {
+            continue;
+        }
+
+        let input = mem::take(&mut buffer);
+
+        if !input.trim().is_empty() {
+            inputs += 1;
+            eval_input(&mut interpreter, &input, &format!("<repl:{}>", inputs));
+        }
+    }
+
+    let _ = writeln!(interpreter.state().io().stdout());
+}
+
+/// Runs one complete input. Each gets its own `name`, so errors in code from an
+/// earlier input point back at it.
+fn eval_input(interpreter: &mut Interpreter, input: &str, name: &str) {
+    let expression = input.trim();
+
+    let result = if grammar::expression(expression).is_ok() {
+        interpreter.eval_or_call_str(expression, name)
+    } else {
+        interpreter.run_str(input, name).map(|_| None)
+    };
+
+    let mut io = interpreter.state().io();
+
+    let written = match result {
+        Ok(Some(value)) => value.println(io.stdout()),
+        Ok(None) => Ok(()),
+        Err(e) => writeln!(io.stderr(), "{}", e.report()),
+    };
+
+    if let Err(e) = written {
+        let _ = writeln!(io.stderr(), "{}", e);
+    }
+}
+
+/// Counts the `:<` blocks and `[` brackets in `input` that have not been
+/// closed yet, ignoring anything inside string literals.
+fn open_blocks(input: &str) -> isize {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => in_string = !in_string,
+            ':' if !in_string && chars.peek() == Some(&'<') => {
+                chars.next();
+                depth += 1;
+            }
+            '>' if !in_string && chars.peek() == Some(&':') => {
+                chars.next();
+                depth -= 1;
+            }
+            '[' if !in_string => depth += 1,
+            ']' if !in_string => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth
+}
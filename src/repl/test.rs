@@ -0,0 +1,33 @@
+use crate::repl::open_blocks;
+
+#[test]
+fn test_single_line_is_complete() {
+    assert_eq!(open_blocks("x squanch 10\n"), 0);
+}
+
+#[test]
+fn test_open_block_is_incomplete() {
+    assert_eq!(open_blocks("foo(x) :<\n"), 1);
+    assert_eq!(open_blocks("while rick :<\n if x :<\n"), 2);
+}
+
+#[test]
+fn test_closed_block_is_complete() {
+    assert_eq!(
+        open_blocks("foo(x) :<\n if x :<\n return 1\n >:\n return 2\n>:\n"),
+        0
+    );
+}
+
+#[test]
+fn test_block_markers_in_strings_are_ignored() {
+    assert_eq!(open_blocks("show me what you got \":<\"\n"), 0);
+    assert_eq!(open_blocks("if x :<\n show me what you got \">:\"\n"), 1);
+}
+
+#[test]
+fn test_open_list_is_incomplete() {
+    assert_eq!(open_blocks("l squanch [\n 1,\n"), 1);
+    assert_eq!(open_blocks("l squanch [\n 1,\n [2]\n]\n"), 0);
+    assert_eq!(open_blocks("show me what you got \"[\"\n"), 0);
+}
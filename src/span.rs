@@ -0,0 +1,140 @@
+use std::{collections::HashMap, fmt::Write};
+
+/// Which of the `Files` a span is in. The default is `<input>`, for code parsed
+/// without registering it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FileId(u32);
+
+/// The name and source of every file an interpreter has parsed, so errors can
+/// say where they happened and show the code there.
+#[derive(Debug)]
+pub struct Files {
+    files: Vec<(String, String)>,
+    ids: HashMap<String, FileId>,
+}
+
+impl Files {
+    /// Registers a file, replacing the source of any file already called
+    /// `name`.
+    pub fn add(&mut self, name: &str, source: &str) -> FileId {
+        match self.ids.get(name) {
+            Some(&file) => {
+                self.files[file.0 as usize].1 = source.into();
+                file
+            }
+            None => {
+                let file = FileId(self.files.len() as u32);
+                self.files.push((name.into(), source.into()));
+                self.ids.insert(name.into(), file);
+                file
+            }
+        }
+    }
+
+    pub fn name(&self, file: FileId) -> &str {
+        &self.get(file).0
+    }
+
+    pub fn source(&self, file: FileId) -> &str {
+        &self.get(file).1
+    }
+
+    // Spans from code another interpreter parsed fall back to `<input>`.
+    fn get(&self, file: FileId) -> &(String, String) {
+        self.files.get(file.0 as usize).unwrap_or(&self.files[0])
+    }
+}
+
+impl Default for Files {
+    fn default() -> Self {
+        let mut files = Self {
+            files: Vec::new(),
+            ids: HashMap::new(),
+        };
+        files.add("<input>", "");
+        files
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub len: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn end(&self) -> usize {
+        self.start + self.len
+    }
+
+    pub fn source_in<'a>(&self, source: &'a str) -> Option<&'a str> {
+        source.get(self.start..self.end())
+    }
+
+    pub fn code_frame(&self, source: &str) -> String {
+        let mut frame = String::new();
+
+        let line = match source.lines().nth(self.line.wrapping_sub(1)) {
+            Some(line) => line,
+            None => return frame,
+        };
+
+        let text = self.source_in(source).unwrap_or("");
+        let text = text.lines().next().unwrap_or("");
+        let carets = text.chars().count().max(1);
+
+        let number = self.line.to_string();
+        let gutter = " ".repeat(number.len());
+
+        writeln!(frame, "{} | {}", number, line).unwrap();
+        write!(
+            frame,
+            "{} | {}{}",
+            gutter,
+            " ".repeat(self.column - 1),
+            "^".repeat(carets)
+        )
+        .unwrap();
+
+        frame
+    }
+}
+
+pub struct LineIndex<'a> {
+    file: FileId,
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(file: FileId, source: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(idx, _)| idx + 1))
+            .collect();
+
+        Self {
+            file,
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn span(&self, start: usize, end: usize) -> Span {
+        let line = match self.line_starts.binary_search(&start) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        let line_start = self.line_starts[line];
+
+        Span {
+            file: self.file,
+            start,
+            len: end - start,
+            line: line + 1,
+            column: self.source[line_start..start].chars().count() + 1,
+        }
+    }
+}
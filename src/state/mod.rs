@@ -1,22 +1,70 @@
 use crate::{
-    error::{ErrorKind, ErrorKindExt, ErrorWithContext, SwResult},
-    expression::Expression,
+    builtins,
+    error::{self, ErrorKind, ErrorKindExt, ErrorValue, ErrorWithContext, SwResult},
+    expression::{Expression, ExpressionKind},
     grammar,
-    statement::{Statement, StatementKind},
-    value::{self, Value},
-    vec_map::VecMap,
+    io::{Io, StdIo},
+    module::Modules,
+    plugin,
+    span::{FileId, Files},
+    statement::{Imports, Iterable, Statement, StatementKind},
+    symbols::{Ident, SymbolTable},
+    value::{self, Closure, Items, Key, Value},
+    vm,
 };
-use std::{borrow, io, mem};
+use rand::{rngs::StdRng, SeedableRng};
+use std::{
+    borrow,
+    cell::{Ref, RefCell, RefMut},
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Instant,
+};
+
+pub(crate) type Scope = Rc<RefCell<SymbolTable<Value>>>;
 
-type Map<K, V> = VecMap<K, V>;
+/// What error messages call a function that wasn't called by name.
+const ANONYMOUS: &str = "<anonymous>";
 
 #[cfg(test)]
 mod test;
 
 pub struct State {
-    symbols: Map<String, Value>,
+    // The scope chain, innermost last. The scopes of running for loops sit on
+    // top of the local one.
+    scopes: Vec<Scope>,
+    loop_vars: Vec<Ident>,
+    global_names: Vec<Ident>,
+    nonlocal_names: Vec<Ident>,
     last_return: Option<Value>,
+    loops: Vec<Option<Ident>>,
+    signal: Option<Signal>,
     libraries: Vec<libloading::Library>,
+    io: Rc<RefCell<dyn Io>>,
+    rng: Rc<RefCell<StdRng>>,
+    started: Instant,
+    modules: Rc<RefCell<Modules>>,
+    files: Rc<RefCell<Files>>,
+    use_vm: bool,
+    legacy_abi: bool,
+    // `ANONYMOUS`, interned once instead of on every call.
+    anonymous: Ident,
+}
+
+/// A `break` or `continue` on its way out to the loop it belongs to.
+#[derive(Debug, Clone, Copy)]
+enum Signal {
+    Break(Option<Ident>),
+    Continue(Option<Ident>),
+}
+
+impl Signal {
+    fn label(self) -> Option<Ident> {
+        match self {
+            Signal::Break(label) | Signal::Continue(label) => label,
+        }
+    }
 }
 
 // macro_rules! error {
@@ -46,98 +94,122 @@ pub struct State {
 impl State {
     pub fn list_index<'a>(
         &'a self,
-        list_name: &str,
+        list_name: Ident,
         exp: &Expression,
     ) -> SwResult<borrow::Cow<'a, Value>> {
         let inner_expression_value = exp.evaluate(self)?.into_owned();
-        match self.symbols.get(list_name) {
-            Some(symbol) => match *symbol {
-                Value::List(ref l) => {
-                    if let Value::Int(i) = inner_expression_value {
-                        let index = i as usize;
-                        if index < l.len() {
-                            Ok(borrow::Cow::Borrowed(&l[index]))
-                        } else {
-                            Err(ErrorKind::IndexOutOfBounds {
-                                len: l.len(),
-                                index,
-                            }
-                            .into())
-                        }
-                    } else {
-                        Err(ErrorKind::UnexpectedType {
-                            expected: value::Type::Int,
-                            actual: inner_expression_value.get_type(),
-                        }
-                        .into())
-                    }
-                }
-                Value::Str(ref s) => {
-                    if let Value::Int(i) = inner_expression_value {
-                        let index = i as usize;
-                        let chars: Vec<char> = s.chars().collect();
-
-                        if index < chars.len() {
-                            Ok(borrow::Cow::Owned(Value::Str(chars[index].to_string())))
-                        } else {
-                            Err(ErrorKind::IndexOutOfBounds {
-                                len: chars.len(),
-                                index,
-                            }
-                            .into())
-                        }
-                    } else {
-                        Err(ErrorKind::UnexpectedType {
-                            expected: value::Type::Int,
-                            actual: inner_expression_value.get_type(),
-                        }
-                        .into())
-                    }
-                }
-                _ => Err(ErrorKind::IndexUnindexable(symbol.get_type()).into()),
-            },
-            None => Err(ErrorKind::UnknownVariable(list_name.to_string()).into()),
+        let list = self.get(list_name)?;
+
+        list.index(&inner_expression_value).map(borrow::Cow::Owned)
+    }
+
+    pub fn call_function(&self, name: Ident, args: &[Expression]) -> SwResult<Value> {
+        let mut call_args = Vec::new();
+
+        for x in args {
+            call_args.push(x.evaluate(self)?.into_owned());
         }
+
+        let function = self.get(name)?.clone();
+
+        self.call_value(name, &function, call_args)
     }
 
-    pub fn call_function(&self, name: &str, args: &[Expression]) -> SwResult<Value> {
+    pub fn call_expression(&self, callee: &Expression, args: &[Expression]) -> SwResult<Value> {
         let mut call_args = Vec::new();
 
         for x in args {
             call_args.push(x.evaluate(self)?.into_owned());
         }
 
-        if let Value::NativeFunction(ref funk) = *self.get(name)? {
-            return funk.call(&mut call_args);
+        let function = callee.evaluate(self)?;
+        let name = match callee.kind {
+            ExpressionKind::Variable(name) => name,
+            _ => self.anonymous,
+        };
+
+        self.call_value(name, &function, call_args)
+    }
+
+    /// Evaluates `expression`, except that a call to a function that doesn't
+    /// return anything gives `None` instead of failing.
+    pub(crate) fn evaluate_call(&self, expression: &Expression) -> SwResult<Option<Value>> {
+        let (name, callee, args) = match expression.kind {
+            ExpressionKind::FunctionCall(name, ref args) => (name, None, args),
+            ExpressionKind::Call(ref callee, ref args) => {
+                let name = match callee.kind {
+                    ExpressionKind::Variable(name) => name,
+                    _ => self.anonymous,
+                };
+                (name, Some(callee), args)
+            }
+            _ => {
+                return expression
+                    .evaluate(self)
+                    .map(|value| Some(value.into_owned()))
+            }
+        };
+
+        let mut call_args = Vec::new();
+
+        for x in args {
+            call_args.push(x.evaluate(self)?.into_owned());
         }
 
-        match self.get(name)? {
-            Value::Function(ref params, ref body) => {
-                if args.len() != params.len() {
+        let function = match callee {
+            Some(callee) => callee.evaluate(self)?.into_owned(),
+            None => self.get(name)?.clone(),
+        };
+
+        self.invoke(name, &function, call_args)
+    }
+
+    pub(crate) fn call_value(
+        &self,
+        name: Ident,
+        function: &Value,
+        call_args: Vec<Value>,
+    ) -> SwResult<Value> {
+        match self.invoke(name, function, call_args)? {
+            Some(val) => Ok(val),
+            None => Err(ErrorKind::NoReturn(name.to_string()).into()),
+        }
+    }
+
+    /// Calls `function`, giving back `None` if it finished without returning.
+    pub(crate) fn invoke(
+        &self,
+        name: Ident,
+        function: &Value,
+        mut call_args: Vec<Value>,
+    ) -> SwResult<Option<Value>> {
+        match *function {
+            Value::NativeFunction(ref funk) => funk.call(self, &mut call_args).map(Some),
+            Value::Function(ref closure) => {
+                if call_args.len() != closure.params.len() {
                     return Err(ErrorKind::InvalidArguments(
                         name.to_string(),
-                        args.len(),
-                        params.len(),
+                        call_args.len(),
+                        closure.params.len(),
                     )
                     .into());
                 }
 
-                let mut child_state = Self::default();
-
-                for (name, arg) in params.iter().zip(call_args) {
-                    child_state.symbols.insert(name.to_string(), arg);
-                }
-
-                child_state.run(body)?;
+                let mut child_state = self.call_frame(closure);
 
-                let last_ret = mem::replace(&mut child_state.last_return, None);
+                if child_state.use_vm {
+                    vm::call(&mut child_state, closure, call_args)?;
+                } else {
+                    for (name, arg) in closure.params.iter().zip(call_args) {
+                        child_state.insert(*name, arg);
+                    }
 
-                match last_ret {
-                    Some(val) => Ok(val),
-                    None => Err(ErrorKind::NoReturn(name.to_string()).into()),
+                    child_state.run(&closure.body)?;
                 }
+
+                Ok(child_state.take_return())
             }
-            val => Err(ErrorKind::UnexpectedType {
+            ref val => Err(ErrorKind::UnexpectedType {
                 expected: value::Type::Function,
                 actual: val.get_type(),
             }
@@ -145,126 +217,182 @@ impl State {
         }
     }
 
-    pub fn get(&self, name: &str) -> SwResult<&Value> {
-        match self.symbols.get(name) {
-            Some(val) => Ok(val),
-            None => Err(ErrorKind::UnknownVariable(name.to_string()).into()),
+    fn scope_of(&self, name: Ident) -> Option<&Scope> {
+        self.scopes
+            .iter()
+            .rev()
+            .find(|scope| scope.borrow().contains_key(&name))
+    }
+
+    fn enclosing_scope_of(&self, name: Ident) -> Option<&Scope> {
+        let enclosing = &self.scopes[..self.scopes.len() - self.loop_vars.len() - 1];
+
+        enclosing
+            .iter()
+            .rev()
+            .find(|scope| scope.borrow().contains_key(&name))
+    }
+
+    fn target_scope(&self, name: Ident) -> &Scope {
+        let local = self.scopes.len() - self.loop_vars.len() - 1;
+
+        if let Some(idx) = self.loop_vars.iter().rposition(|&var| var == name) {
+            &self.scopes[local + 1 + idx]
+        } else if self.global_names.contains(&name) {
+            &self.scopes[0]
+        } else if self.nonlocal_names.contains(&name) {
+            self.enclosing_scope_of(name).unwrap_or(&self.scopes[local])
+        } else {
+            &self.scopes[local]
         }
     }
 
-    pub fn assign(&mut self, str: String, exp: &Expression) -> SwResult<()> {
+    /// Gives the variable of a for loop a scope of its own, so it doesn't
+    /// clobber a variable of the same name or outlive the loop.
+    pub(crate) fn push_loop_scope(&mut self, name: Ident) {
+        self.scopes.push(Scope::default());
+        self.loop_vars.push(name);
+    }
+
+    /// Drops loop scopes until only `depth` are left.
+    pub(crate) fn pop_loop_scopes(&mut self, depth: usize) {
+        while self.loop_vars.len() > depth {
+            self.scopes.pop();
+            self.loop_vars.pop();
+        }
+    }
+
+    pub(crate) fn loop_depth(&self) -> usize {
+        self.loop_vars.len()
+    }
+
+    pub fn get<S>(&self, name: S) -> SwResult<Ref<'_, Value>>
+    where
+        S: Into<Ident>,
+    {
+        let name = name.into();
+
+        for scope in self.scopes.iter().rev() {
+            if let Ok(value) = Ref::filter_map(scope.borrow(), |symbols| symbols.get(&name)) {
+                return Ok(value);
+            }
+        }
+
+        Err(ErrorKind::UnknownVariable(name.to_string()).into())
+    }
+
+    pub fn assign<S>(&mut self, name: S, exp: &Expression) -> SwResult<()>
+    where
+        S: Into<Ident>,
+    {
         let v = exp.evaluate(self)?.into_owned();
-        self.symbols.insert(str, v);
+        self.insert(name, v);
         Ok(())
     }
 
-    fn delete(&mut self, name: &str) -> SwResult<()> {
-        match self.symbols.remove(name) {
+    pub(crate) fn delete(&mut self, name: Ident) -> SwResult<()> {
+        match self.target_scope(name).borrow_mut().remove(&name) {
             Some(_) => Ok(()),
             None => Err(ErrorKind::UnknownVariable(name.to_string()).into()),
         }
     }
 
-    fn print(&mut self, exp: &Expression) -> SwResult<()> {
-        let x = exp.evaluate(self)?;
-        x.println();
+    fn declare_globals(&mut self, names: &[Ident]) {
+        for &name in names {
+            if !self.global_names.contains(&name) {
+                self.global_names.push(name);
+            }
+        }
+    }
+
+    fn declare_nonlocals(&mut self, names: &[Ident]) -> SwResult<()> {
+        for &name in names {
+            if self.enclosing_scope_of(name).is_none() {
+                return Err(ErrorKind::UnknownVariable(name.to_string()).into());
+            }
+
+            if !self.nonlocal_names.contains(&name) {
+                self.nonlocal_names.push(name);
+            }
+        }
+
         Ok(())
     }
 
+    fn print(&mut self, exp: &Expression) -> SwResult<()> {
+        exp.with_value(self, |x| Ok(x.println(self.io.borrow_mut().stdout())?))
+    }
+
     fn print_no_nl(&mut self, exp: &Expression) -> SwResult<()> {
-        let x = exp.evaluate(self)?;
-        x.print();
+        exp.with_value(self, |x| Ok(x.print(self.io.borrow_mut().stdout())?))
+    }
+
+    fn input(&mut self, name: Ident) -> SwResult<()> {
+        let input = self.read_input()?;
+        self.insert(name, input);
+
         Ok(())
     }
 
-    fn input(&mut self, name: String) -> SwResult<()> {
+    pub(crate) fn read_input(&self) -> SwResult<Value> {
         let mut input = String::new();
 
-        io::stdin().read_line(&mut input)?;
+        let mut io = self.io.borrow_mut();
+        io.stdout().flush()?;
+        io.read_line(&mut input)?;
 
-        input = input.trim().to_string();
-        self.symbols.insert(name, Value::Str(input));
-
-        Ok(())
+        Ok(Value::Str(input.trim().to_string()))
     }
 
-    fn list_append(&mut self, list_name: &str, append_exp: &Expression) -> SwResult<()> {
+    fn list_append(&mut self, list_name: Ident, append_exp: &Expression) -> SwResult<()> {
         let to_append = append_exp.evaluate(self)?.into_owned();
-        let list = self.get_list(list_name)?;
+        let mut list = self.get_list(list_name)?;
 
         list.push(to_append);
         Ok(())
     }
 
-    fn get_mut(&mut self, name: &str) -> SwResult<&mut Value> {
-        match self.symbols.get_mut(name) {
-            Some(value) => Ok(value),
+    pub(crate) fn get_mut(&self, name: Ident) -> SwResult<RefMut<'_, Value>> {
+        match self.scope_of(name) {
+            Some(scope) => Ok(RefMut::map(scope.borrow_mut(), |symbols| {
+                symbols.get_mut(&name).unwrap()
+            })),
             None => Err(ErrorKind::UnknownVariable(name.to_string()).into()),
         }
     }
 
-    fn get_list(&mut self, name: &str) -> SwResult<&mut Vec<Value>> {
+    pub(crate) fn get_list(&self, name: Ident) -> SwResult<RefMut<'_, Vec<Value>>> {
         let value = self.get_mut(name)?;
-        match *value {
-            Value::List(ref mut l) => Ok(l),
-            _ => Err(ErrorKind::IndexUnindexable(value.get_type()).into()),
-        }
-    }
-
-    fn get_list_element(&mut self, name: &str, index_exp: &Expression) -> SwResult<&mut Value> {
-        let index = index_exp.try_int(self)? as usize;
-        let value = self.get_mut(name)?;
-
-        match *value {
-            Value::List(ref mut list) => {
-                let len = list.len();
-                if index < len {
-                    Ok(&mut list[index])
-                } else {
-                    Err(ErrorKind::IndexOutOfBounds { len, index }.into())
-                }
-            }
-            ref val => Err(ErrorKind::IndexUnindexable(val.get_type()).into()),
-        }
+        RefMut::filter_map(value, |value| match *value {
+            Value::List(ref mut l) => Some(l),
+            _ => None,
+        })
+        .map_err(|value| ErrorKind::IndexUnindexable(value.get_type()).into())
     }
 
     fn list_assign(
         &mut self,
-        list_name: &str,
-        index_exp: &Expression,
+        list_name: Ident,
+        index_exps: &[Expression],
         assign_exp: &Expression,
     ) -> SwResult<()> {
         let to_assign = assign_exp.evaluate(self)?.into_owned();
-        let element = self.get_list_element(list_name, index_exp)?;
+        let path = self.index_path(index_exps)?;
 
-        *element = to_assign;
-        Ok(())
+        self.get_mut(list_name)?.assign_nested(&path, to_assign)
     }
 
-    fn list_delete(&mut self, list_name: &str, index_exp: &Expression) -> SwResult<()> {
-        let index_value = index_exp.evaluate(self)?;
+    fn list_delete(&mut self, list_name: Ident, index_exps: &[Expression]) -> SwResult<()> {
+        let path = self.index_path(index_exps)?;
 
-        if let Value::Int(i) = *index_value {
-            let index = i as usize;
-            let list = self.get_list(list_name)?;
+        self.get_mut(list_name)?.delete_nested(&path)
+    }
 
-            if index < list.len() {
-                list.remove(index);
-                Ok(())
-            } else {
-                Err(ErrorKind::IndexOutOfBounds {
-                    len: list.len(),
-                    index,
-                }
-                .into())
-            }
-        } else {
-            Err(ErrorKind::UnexpectedType {
-                expected: value::Type::Int,
-                actual: index_value.get_type(),
-            }
-            .into())
-        }
+    fn index_path(&self, index_exps: &[Expression]) -> SwResult<Vec<Value>> {
+        index_exps
+            .iter()
+            .map(|exp| exp.evaluate(self).map(borrow::Cow::into_owned))
+            .collect()
     }
 
     fn exec_if(
@@ -280,9 +408,8 @@ impl State {
                 if b {
                     self.run(if_body)?;
                 } else {
-                    match *else_body {
-                        Option::Some(ref s) => self.run(s)?,
-                        Option::None => {}
+                    if let Option::Some(ref s) = *else_body {
+                        self.run(s)?;
                     }
                 }
             }
@@ -301,58 +428,194 @@ impl State {
     fn exec_while(
         &mut self,
         statement: &Statement,
+        label: Option<Ident>,
         bool: &Expression,
         body: &[Statement],
     ) -> SwResult<()> {
-        let mut condition = bool.try_bool(self)?;
+        self.in_loop(label, |state| {
+            let mut condition = bool.try_bool(state)?;
 
-        while condition {
-            self.run(body)?;
-            if self.last_return.is_some() {
-                return Ok(());
+            while condition {
+                if !state.iterate(label, body)? {
+                    return Ok(());
+                }
+                condition = bool.try_bool(state).with_error_ctx(statement)?;
             }
-            condition = bool.try_bool(self).with_error_ctx(statement)?;
+
+            Ok(())
+        })
+    }
+
+    fn exec_for(
+        &mut self,
+        label: Option<Ident>,
+        name: Ident,
+        iterable: &Iterable,
+        body: &[Statement],
+    ) -> SwResult<()> {
+        let items = match *iterable {
+            Iterable::Each(ref exp) => exp.evaluate(self)?.into_owned().items()?,
+            Iterable::Range(ref start, ref end) => {
+                Items::range(&*start.evaluate(self)?, &*end.evaluate(self)?)?
+            }
+        };
+
+        let depth = self.loop_depth();
+        self.push_loop_scope(name);
+
+        let result = self.in_loop(label, |state| {
+            for item in items {
+                state.insert(name, item);
+                if !state.iterate(label, body)? {
+                    return Ok(());
+                }
+            }
+
+            Ok(())
+        });
+
+        self.pop_loop_scopes(depth);
+        result
+    }
+
+    fn exec_labeled(&mut self, label: Ident, statement: &Statement) -> SwResult<()> {
+        match statement.kind {
+            StatementKind::While(ref bool, ref body) => {
+                self.exec_while(statement, Some(label), bool, body)
+            }
+            StatementKind::For(name, ref iterable, ref body) => {
+                self.exec_for(Some(label), name, iterable, body)
+            }
+            _ => Ok(self.execute(statement)?),
         }
+    }
 
-        Ok(())
+    /// Runs `looping` with `label` as the innermost loop that `break` and
+    /// `continue` can reach.
+    fn in_loop<F>(&mut self, label: Option<Ident>, looping: F) -> SwResult<()>
+    where
+        F: FnOnce(&mut Self) -> SwResult<()>,
+    {
+        self.loops.push(label);
+        let result = looping(self);
+        self.loops.pop();
+        result
+    }
+
+    /// Runs one pass of a loop body, giving back whether the loop goes on.
+    fn iterate(&mut self, label: Option<Ident>, body: &[Statement]) -> SwResult<bool> {
+        self.run(body)?;
+        if self.last_return.is_some() {
+            return Ok(false);
+        }
+
+        match self.signal {
+            None => Ok(true),
+            Some(signal) if signal.label().is_none() || signal.label() == label => {
+                self.signal = None;
+                Ok(matches!(signal, Signal::Continue(_)))
+            }
+            Some(_) => Ok(false),
+        }
     }
 
-    fn catch(&mut self, try_block: &[Statement], catch: &[Statement]) -> SwResult<()> {
-        match self.run(try_block) {
-            Err(_) => self.run(catch)?,
+    fn loop_control(&mut self, signal: Signal) -> SwResult<()> {
+        match signal.label() {
+            None if self.loops.is_empty() => {
+                let keyword = match signal {
+                    Signal::Break(_) => "break",
+                    Signal::Continue(_) => "continue",
+                };
+
+                return Err(ErrorKind::OutsideLoop(keyword.into()).into());
+            }
+            Some(label) if !self.loops.contains(&Some(label)) => {
+                return Err(ErrorKind::UnknownLabel(label.to_string()).into());
+            }
             _ => {}
         }
 
+        self.signal = Some(signal);
+        Ok(())
+    }
+
+    fn catch(
+        &mut self,
+        try_block: &[Statement],
+        name: Option<Ident>,
+        catch: &[Statement],
+    ) -> SwResult<()> {
+        if let Err(e) = self.run(try_block) {
+            if let Some(name) = name {
+                let error = ErrorValue::new(&e, &self.files());
+                self.insert(name, Value::Error(error));
+            }
+
+            self.run(catch)?;
+        }
+
         Ok(())
     }
 
+    fn rethrow(&mut self, exp: &Expression) -> SwResult<()> {
+        let value = exp.evaluate(self)?.into_owned();
+        Err(error::rethrow(value))
+    }
+
+    fn throw(&mut self, exp: &Expression) -> SwResult<()> {
+        let value = exp.evaluate(self)?.into_owned();
+        Err(ErrorKind::Thrown(value).into())
+    }
+
     pub fn execute(&mut self, statement: &Statement) -> Result<(), ErrorWithContext> {
         match statement.kind {
-            StatementKind::Input(ref s) => self.input(s.to_string()),
-            StatementKind::ListAssign(ref s, ref index_exp, ref assign_exp) => {
-                self.list_assign(s, index_exp, assign_exp)
+            StatementKind::Input(s) => self.input(s),
+            StatementKind::ListAssign(s, ref index_exps, ref assign_exp) => {
+                self.list_assign(s, index_exps, assign_exp)
             }
-            StatementKind::ListAppend(ref s, ref append_exp) => self.list_append(s, append_exp),
-            StatementKind::ListDelete(ref name, ref idx) => self.list_delete(name, idx),
-            StatementKind::ListNew(ref s) => {
-                self.symbols.insert(s.clone(), Value::List(Vec::new()));
+            StatementKind::ListAppend(s, ref append_exp) => self.list_append(s, append_exp),
+            StatementKind::ListDelete(name, ref idxs) => self.list_delete(name, idxs),
+            StatementKind::ListNew(s) => {
+                self.insert(s, Value::List(Vec::new()));
+
+                Ok(())
+            }
+            StatementKind::MapNew(s) => {
+                self.insert(s, value::Map::new());
 
                 Ok(())
             }
             StatementKind::If(ref bool, ref if_body, ref else_body) => {
                 self.exec_if(bool, if_body, else_body)
             }
-            StatementKind::While(ref bool, ref body) => self.exec_while(statement, bool, body),
-            StatementKind::Assignment(ref name, ref value) => self.assign(name.clone(), value),
-            StatementKind::Delete(ref name) => self.delete(name),
+            StatementKind::While(ref bool, ref body) => {
+                self.exec_while(statement, None, bool, body)
+            }
+            StatementKind::For(name, ref iterable, ref body) => {
+                self.exec_for(None, name, iterable, body)
+            }
+            StatementKind::Labeled(label, ref inner) => self.exec_labeled(label, inner),
+            StatementKind::Break(label) => self.loop_control(Signal::Break(label)),
+            StatementKind::Continue(label) => self.loop_control(Signal::Continue(label)),
+            StatementKind::Assignment(name, ref value) => self.assign(name, value),
+            StatementKind::Delete(name) => self.delete(name),
             StatementKind::Print(ref exp) => self.print(exp),
             StatementKind::PrintNoNl(ref exp) => self.print_no_nl(exp),
-            StatementKind::Catch(ref try_block, ref catch) => self.catch(try_block, catch),
-            StatementKind::Function(ref name, ref args, ref body) => {
-                self.symbols
-                    .insert(name.clone(), Value::Function(args.clone(), body.clone()));
+            StatementKind::Catch(ref try_block, name, ref catch) => {
+                self.catch(try_block, name, catch)
+            }
+            StatementKind::Rethrow(ref exp) => self.rethrow(exp),
+            StatementKind::Throw(ref exp) => self.throw(exp),
+            StatementKind::Function(name, ref args, ref body) => {
+                let closure = self.closure(args, body);
+                self.insert(name, closure);
+                Ok(())
+            }
+            StatementKind::Global(ref names) => {
+                self.declare_globals(names);
                 Ok(())
             }
+            StatementKind::Nonlocal(ref names) => self.declare_nonlocals(names),
             StatementKind::Return(ref expr) => {
                 let val = expr.evaluate(self).with_error_ctx(statement)?;
                 self.last_return = Some(val.into_owned());
@@ -360,26 +623,35 @@ impl State {
                 Ok(())
             }
 
-            StatementKind::FunctionCall(ref name, ref args) => {
+            StatementKind::FunctionCall(name, ref args) => {
                 self.call_function(name, args).map(|_| ())
             }
+            StatementKind::Call(ref call) => call.evaluate(self).map(|_| ()),
             StatementKind::DylibLoad(ref lib_path, ref functions) => {
                 self.dylib_load(lib_path, functions)
             }
+            StatementKind::Import(ref path, ref imports) => {
+                self.import(statement.span().file, path, imports)
+            }
         }
         .with_error_ctx(statement)
     }
 
     pub fn run(&mut self, statements: &[Statement]) -> Result<(), ErrorWithContext> {
+        if self.use_vm {
+            vm::run(self, statements)
+        } else {
+            self.walk(statements)
+        }
+    }
+
+    fn walk(&mut self, statements: &[Statement]) -> Result<(), ErrorWithContext> {
         for statement in statements {
-            match self.execute(statement) {
-                Err(e) => return Err(e),
-                Ok(()) => {}
-            }
+            self.execute(statement)?;
             if let StatementKind::Return(_) = statement.kind {
                 return Ok(());
             }
-            if self.last_return.is_some() {
+            if self.last_return.is_some() || self.signal.is_some() {
                 return Ok(());
             }
         }
@@ -399,18 +671,38 @@ impl State {
                     }
                 })?;
 
-            if **compat != crate::LIBSCHWIFT_ABI_COMPAT {
-                return Err(ErrorKind::IncompatibleAbi(**compat).into());
-            }
+            let free = match **compat {
+                plugin::ABI_VERSION => {
+                    let free: libloading::Symbol<plugin::SwFreeFn> =
+                        dylib.get(plugin::FREE_SYMBOL)?;
+                    Some(free.into_raw())
+                }
+                plugin::LEGACY_ABI_VERSION if self.legacy_abi => None,
+                plugin::LEGACY_ABI_VERSION => {
+                    return Err(ErrorKind::LegacyAbi(lib_path.into()).into())
+                }
+                compat => return Err(ErrorKind::IncompatibleAbi(compat).into()),
+            };
 
             for statement in functions {
                 match statement.kind {
-                    StatementKind::FunctionCall(ref name, _) => {
-                        let wrapped_func: libloading::Symbol<value::_Func> =
-                            dylib.get(name.as_bytes())?;
+                    StatementKind::FunctionCall(name, _) => {
+                        let name_bytes = name.as_str().as_bytes();
+
+                        let func = match free {
+                            Some(ref free) => {
+                                let wrapped_func: libloading::Symbol<plugin::SwFn> =
+                                    dylib.get(name_bytes)?;
+                                value::Func::c_abi(wrapped_func.into_raw(), free.clone())
+                            }
+                            None => {
+                                let wrapped_func: libloading::Symbol<value::_Func> =
+                                    dylib.get(name_bytes)?;
+                                value::Func::new(wrapped_func.into_raw())
+                            }
+                        };
 
-                        let func = wrapped_func.into_raw();
-                        self.insert(name.as_str(), value::Func::new(func));
+                        self.insert(name, func);
                     }
                     _ => return Err(ErrorKind::NonFunctionCallInDylib(statement.clone()).into()),
                 }
@@ -422,6 +714,110 @@ impl State {
         Ok(())
     }
 
+    fn import(&mut self, importer: FileId, path: &str, imports: &Imports) -> SwResult<()> {
+        let module = self.load_module(importer, path)?;
+
+        match *imports {
+            Imports::Module(name) => {
+                let name = match name {
+                    Some(name) => name,
+                    None => {
+                        let stem = Path::new(path).file_stem().unwrap_or_default();
+                        Ident::intern(&stem.to_string_lossy())
+                    }
+                };
+
+                self.insert(name, module);
+            }
+            Imports::Names(ref names) => {
+                for &name in names {
+                    let value = match module {
+                        Value::Map(ref exports) => exports.get(&Key::Str(name.to_string())),
+                        _ => None,
+                    };
+
+                    match value {
+                        Some(value) => self.insert(name, value.clone()),
+                        None => {
+                            return Err(
+                                ErrorKind::MissingExport(path.into(), name.to_string()).into()
+                            )
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Runs the module at `path` the first time it is imported and gives back
+    /// a map of its top level definitions.
+    fn load_module(&self, importer: FileId, path: &str) -> SwResult<Value> {
+        let file = self
+            .modules
+            .borrow()
+            .resolve(path, Path::new(self.files().name(importer)))?;
+        let key = fs::canonicalize(&file)?;
+
+        if let Some(module) = self.modules.borrow().get(&key) {
+            return Ok(module);
+        }
+
+        let name = file.display().to_string();
+        self.modules.borrow_mut().enter(key.clone(), name.clone())?;
+        let module = self.run_module(&file, &name);
+        self.modules.borrow_mut().leave();
+
+        let module = module?;
+        self.modules.borrow_mut().finish(key, module.clone());
+        Ok(module)
+    }
+
+    fn run_module(&self, file: &Path, name: &str) -> SwResult<Value> {
+        let source = fs::read_to_string(file)?;
+        let file = self.add_file(name, &source);
+        let statements =
+            grammar::parse(&source, file).map_err(|error| ErrorKind::BrokenModule {
+                module: name.into(),
+                error,
+            })?;
+
+        let mut module = self.call_frame_with(vec![Scope::default()]);
+        builtins::register(&mut module);
+        module.run(&statements)?;
+
+        self.modules.borrow_mut().keep(&mut module.libraries);
+
+        let exports = module.scopes[0]
+            .borrow()
+            .iter()
+            .filter(|(name, value)| match value {
+                Value::NativeFunction(ref f) => f.builtin_name() != Some(name.as_str()),
+                _ => true,
+            })
+            .map(|(name, value)| (Key::Str(name.to_string()), value.clone()))
+            .collect();
+
+        Ok(Value::Map(exports))
+    }
+
+    pub fn add_search_path<P>(&mut self, dir: P)
+    where
+        P: Into<PathBuf>,
+    {
+        self.modules.borrow_mut().add_search_path(dir.into());
+    }
+
+    /// Every file this interpreter has parsed code from.
+    pub fn files(&self) -> Ref<'_, Files> {
+        self.files.borrow()
+    }
+
+    pub(crate) fn add_file(&self, name: &str, source: &str) -> FileId {
+        self.files.borrow_mut().add(name, source)
+    }
+
     pub fn parse_args(&mut self, args: &[&str]) {
         let mut value_args = Vec::new();
 
@@ -429,28 +825,134 @@ impl State {
             value_args.push(grammar::value(arg).unwrap_or_else(|_| Value::Str((*arg).into())));
         }
 
-        self.symbols.insert("argv".into(), value_args.into());
+        self.insert("argv", value_args);
     }
 
     pub fn insert<S, V>(&mut self, name: S, value: V)
     where
-        S: Into<String>,
+        S: Into<Ident>,
         V: Into<Value>,
     {
-        self.symbols.insert(name.into(), value.into());
+        let name = name.into();
+        self.target_scope(name)
+            .borrow_mut()
+            .insert(name, value.into());
+    }
+
+    pub fn use_vm(&mut self, enabled: bool) {
+        self.use_vm = enabled;
+    }
+
+    /// Lets ABI 2 microverses load. Nothing checks that they were built with
+    /// the same compiler and schwift version, so whoever turns this on vouches
+    /// for that.
+    pub fn allow_legacy_abi(&mut self, enabled: bool) {
+        self.legacy_abi = enabled;
+    }
+
+    /// A function value that captures the current scope chain.
+    pub(crate) fn closure(&self, params: &[Ident], body: &[Statement]) -> Value {
+        let closure = Closure::new(params.to_vec(), body.to_vec(), self.scopes.clone());
+        Value::Function(Rc::new(closure))
+    }
+
+    /// The name of functions that weren't called by name.
+    pub(crate) fn anonymous(&self) -> Ident {
+        self.anonymous
+    }
+
+    pub(crate) fn set_return(&mut self, value: Value) {
+        self.last_return = Some(value);
+    }
+
+    /// Ends a call, handing back whatever it returned.
+    pub(crate) fn take_return(&mut self) -> Option<Value> {
+        self.last_return.take()
+    }
+
+    pub fn io(&self) -> std::cell::RefMut<'_, dyn Io> {
+        self.io.borrow_mut()
+    }
+
+    pub fn set_io<I>(&mut self, io: I)
+    where
+        I: Io + 'static,
+    {
+        self.io = Rc::new(RefCell::new(io));
     }
 
     pub fn new() -> Self {
-        Self::default()
+        let mut state = Self::default();
+        builtins::register(&mut state);
+        state
+    }
+
+    pub fn with_io<I>(io: I) -> Self
+    where
+        I: Io + 'static,
+    {
+        let mut state = Self::new();
+        state.set_io(io);
+        state
+    }
+
+    pub(crate) fn rng(&self) -> RefMut<'_, StdRng> {
+        self.rng.borrow_mut()
+    }
+
+    /// When the interpreter started, shared by every call frame.
+    pub(crate) fn started(&self) -> Instant {
+        self.started
+    }
+
+    pub(crate) fn call_frame(&self, closure: &Closure) -> Self {
+        let mut scopes = closure.scopes.clone();
+        scopes.push(Scope::default());
+
+        self.call_frame_with(scopes)
+    }
+
+    fn call_frame_with(&self, scopes: Vec<Scope>) -> Self {
+        Self {
+            scopes,
+            loop_vars: Vec::new(),
+            global_names: Vec::new(),
+            nonlocal_names: Vec::new(),
+            last_return: None,
+            loops: Vec::new(),
+            signal: None,
+            libraries: Vec::new(),
+            io: Rc::clone(&self.io),
+            rng: Rc::clone(&self.rng),
+            started: self.started,
+            modules: Rc::clone(&self.modules),
+            files: Rc::clone(&self.files),
+            use_vm: self.use_vm,
+            legacy_abi: self.legacy_abi,
+            anonymous: self.anonymous,
+        }
     }
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
-            symbols: Map::new(),
+            scopes: vec![Scope::default()],
+            loop_vars: Vec::new(),
+            global_names: Vec::new(),
+            nonlocal_names: Vec::new(),
             last_return: None,
+            loops: Vec::new(),
+            signal: None,
             libraries: Vec::new(),
+            io: Rc::new(RefCell::new(StdIo::new())),
+            rng: Rc::new(RefCell::new(StdRng::from_entropy())),
+            started: Instant::now(),
+            modules: Rc::default(),
+            files: Rc::default(),
+            use_vm: false,
+            legacy_abi: false,
+            anonymous: Ident::intern(ANONYMOUS),
         }
     }
 }
@@ -1,19 +1,29 @@
 use crate::{
-    error::ErrorKind as EKind,
+    error::{ErrorKind as EKind, ErrorWithContext},
     expression::Expression as Exp,
     grammar,
+    io::{SharedBuffer, Streams},
     state::State,
     statement::{Statement, StatementKind as Kind},
-    value::Value,
+    value::{self, Value},
 };
 
+fn run_err(code: &str) -> ErrorWithContext {
+    let mut state = State::new();
+    let code = grammar::file(code).unwrap();
+    state.run(&code).unwrap_err()
+}
+
 #[test]
 fn test_assignment_adds_to_symbol_table() {
     let mut state = State::new();
 
     let statement = Statement::tnew(Kind::assignment("x", 10));
     state.execute(&statement).unwrap();
-    assert_eq!(state.symbols.get("x"), Some(&(Value::new(10))));
+    assert_eq!(
+        state.scopes[0].borrow().get(&"x".into()),
+        Some(&(Value::new(10)))
+    );
 }
 
 #[test]
@@ -22,11 +32,14 @@ fn test_remove_from_symbol_table() {
 
     let statement = Statement::tnew(Kind::assignment("x", 10));
     state.execute(&statement).unwrap();
-    assert_eq!(state.symbols.get("x"), Some(&(Value::new(10))));
+    assert_eq!(
+        state.scopes[0].borrow().get(&"x".into()),
+        Some(&(Value::new(10)))
+    );
 
     let delete = Statement::tnew(Kind::delete("x"));
     state.execute(&delete).unwrap();
-    assert_eq!(state.symbols.get("x"), None);
+    assert_eq!(state.scopes[0].borrow().get(&"x".into()), None);
 }
 
 #[test]
@@ -42,7 +55,7 @@ fn test_get_returns_error_if_not_present() {
     let state = State::new();
 
     assert_eq!(
-        state.get("x"),
+        state.get("x").map(|x| x.clone()),
         Err(EKind::UnknownVariable("x".to_string()).into())
     );
 }
@@ -157,3 +170,766 @@ fn test_early_return_while() {
     assert_eq!(*state.get("a").unwrap(), Value::new(false));
     assert_eq!(*state.get("b").unwrap(), Value::new(true));
 }
+
+#[test]
+fn test_print_goes_to_io_backend() {
+    let stdout = SharedBuffer::new();
+    let mut state = State::with_io(Streams::new(&b""[..], stdout.clone(), Vec::new()));
+
+    let code = grammar::file(
+        r#"
+    greet(name) :<
+        show me what you got! "Hello, "
+        show me what you got name
+        return rick
+    >:
+
+    x squanch greet("Rick")
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(stdout.contents(), "Hello, Rick\n");
+}
+
+#[test]
+fn test_input_reads_from_io_backend() {
+    let stdout = SharedBuffer::new();
+    let mut state = State::with_io(Streams::new(
+        &b"Morty\nSummer\n"[..],
+        stdout.clone(),
+        Vec::new(),
+    ));
+
+    let code = grammar::file(
+        r#"
+    portal gun first
+    portal gun second
+    show me what you got (first + second)
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("second").unwrap(), Value::new("Summer"));
+    assert_eq!(stdout.contents(), "MortySummer\n");
+}
+
+#[test]
+fn test_functions_can_recurse() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    fib(n) :<
+        if (n less 2) :<
+            return n
+        >:
+        return (fib((n - 1)) + fib((n - 2)))
+    >:
+
+    x squanch fib(15)
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("x").unwrap(), Value::new(610));
+}
+
+#[test]
+fn test_functions_can_call_each_other() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    isEven(n) :<
+        if (n == 0) :<
+            return rick
+        >:
+        return isOdd((n - 1))
+    >:
+
+    isOdd(n) :<
+        if (n == 0) :<
+            return morty
+        >:
+        return isEven((n - 1))
+    >:
+
+    offset squanch 3
+    shifted(n) :<
+        return (n + offset)
+    >:
+
+    x squanch isEven(10)
+    y squanch isOdd(7)
+    z squanch shifted(4)
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("x").unwrap(), Value::new(true));
+    assert_eq!(*state.get("y").unwrap(), Value::new(true));
+    assert_eq!(*state.get("z").unwrap(), Value::new(7));
+}
+
+#[test]
+fn test_assignment_in_function_is_local() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    x squanch 1
+    shadow() :<
+        x squanch 2
+        return x
+    >:
+
+    y squanch shadow()
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("x").unwrap(), Value::new(1));
+    assert_eq!(*state.get("y").unwrap(), Value::new(2));
+}
+
+#[test]
+fn test_global_assigns_outer_variable() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    count squanch 0
+    bump() :<
+        global count
+        count squanch (count + 1)
+        return count
+    >:
+
+    bump()
+    bump()
+    last squanch bump()
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("count").unwrap(), Value::new(3));
+    assert_eq!(*state.get("last").unwrap(), Value::new(3));
+}
+
+#[test]
+fn test_closure_captures_enclosing_variables() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    adder(n) :<
+        add(x) :<
+            return (x + n)
+        >:
+        return add
+    >:
+
+    addFive squanch adder(5)
+    addTen squanch adder(10)
+    x squanch addFive(1)
+    y squanch addTen(1)
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("x").unwrap(), Value::new(6));
+    assert_eq!(*state.get("y").unwrap(), Value::new(11));
+}
+
+#[test]
+fn test_closure_counter() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    makeCounter() :<
+        count squanch 0
+        counter() :<
+            nonlocal count
+            count squanch (count + 1)
+            return count
+        >:
+        return counter
+    >:
+
+    first squanch makeCounter()
+    second squanch makeCounter()
+    first()
+    first()
+    x squanch first()
+    y squanch second()
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("x").unwrap(), Value::new(3));
+    assert_eq!(*state.get("y").unwrap(), Value::new(1));
+}
+
+#[test]
+fn test_nonlocal_requires_enclosing_variable() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    broken() :<
+        nonlocal missing
+        return rick
+    >:
+
+    broken()
+    "#,
+    )
+    .unwrap();
+
+    let err = state.run(&code).unwrap_err();
+    assert_eq!(*err.kind(), EKind::UnknownVariable("missing".into()));
+}
+
+#[test]
+fn test_catch_binds_error_value() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    normal plan :<
+        l on a cob
+        x squanch l[3]
+    >: plan for failure e :<
+        kind squanch e["kind"]
+        message squanch e["message"]
+        line squanch e["line"]
+        column squanch e["column"]
+    >:
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("kind").unwrap(), Value::new("IndexOutOfBounds"));
+    assert_eq!(
+        *state.get("message").unwrap(),
+        Value::new(
+            "Y-you can't just keep asking for more, Morty! You want 3, but your cob only has 0 kernels on it!"
+        )
+    );
+    assert_eq!(*state.get("line").unwrap(), Value::new(4));
+    assert_eq!(*state.get("column").unwrap(), Value::new(19));
+}
+
+#[test]
+fn test_rethrow_keeps_original_error() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    normal plan :<
+        normal plan :<
+            show me what you got nope
+        >: plan for failure inner :<
+            if (inner["kind"] == "UnknownVariable") :<
+                rethrow inner
+            >:
+        >:
+    >: plan for failure outer :<
+        kind squanch outer["kind"]
+        same squanch (outer == inner)
+    >:
+
+    rethrow inner
+    "#,
+    )
+    .unwrap();
+
+    let err = state.run(&code).unwrap_err();
+    assert_eq!(*state.get("kind").unwrap(), Value::new("UnknownVariable"));
+    assert_eq!(*state.get("same").unwrap(), Value::new(true));
+    assert_eq!(err.kind().name(), "UnknownVariable");
+    assert_eq!(err.span().line, 4);
+}
+
+#[test]
+fn test_rethrow_requires_error() {
+    let mut state = State::new();
+
+    let code = grammar::file("rethrow 10").unwrap();
+
+    let err = state.run(&code).unwrap_err();
+    assert_eq!(
+        *err.kind(),
+        EKind::UnexpectedType {
+            expected: value::Type::Error,
+            actual: value::Type::Int,
+        }
+    );
+}
+
+#[test]
+fn test_throw_from_function_is_catchable() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    check(x) :<
+        if (x less 0) :<
+            throw "negative"
+        >:
+        return x
+    >:
+
+    normal plan :<
+        y squanch check(-1)
+    >: plan for failure e :<
+        kind squanch e["kind"]
+        message squanch e["message"]
+        payload squanch e["value"]
+    >:
+
+    check(-2)
+    "#,
+    )
+    .unwrap();
+
+    let err = state.run(&code).unwrap_err();
+    assert_eq!(*state.get("kind").unwrap(), Value::new("Thrown"));
+    assert_eq!(
+        *state.get("message").unwrap(),
+        Value::new("Your program threw negative")
+    );
+    assert_eq!(*state.get("payload").unwrap(), Value::new("negative"));
+    assert_eq!(*err.kind(), EKind::Thrown(Value::new("negative")));
+}
+
+#[test]
+fn test_maps() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    m on a plumbus
+    m["rick"] squanch "sanchez"
+    m[137] squanch "c-137"
+    m["morty"] squanch 14
+    m["morty"] squanch (m["morty"] + 1)
+    squanch m[137]
+    name squanch m["rick"]
+    size squanch m squanch
+
+    other on a plumbus
+    other["morty"] squanch 15
+    other["rick"] squanch "sanchez"
+    same squanch (m == other)
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("name").unwrap(), Value::new("sanchez"));
+    assert_eq!(*state.get("size").unwrap(), Value::new(2));
+    assert_eq!(*state.get("same").unwrap(), Value::new(true));
+    assert_eq!(
+        state.get("m").unwrap().to_string(),
+        r#"{"morty": 15, "rick": "sanchez"}"#
+    );
+}
+
+#[test]
+fn test_map_errors() {
+    assert_eq!(
+        *run_err("m on a plumbus\nx squanch m[\"nope\"]").kind(),
+        EKind::MissingKey("\"nope\"".into())
+    );
+    assert_eq!(
+        *run_err("m on a plumbus\nsquanch m[1]").kind(),
+        EKind::MissingKey("1".into())
+    );
+    assert_eq!(
+        *run_err("m on a plumbus\nm[rick] squanch 1").kind(),
+        EKind::UnexpectedType {
+            expected: value::Type::Union(Box::new(value::Type::Str), Box::new(value::Type::Int)),
+            actual: value::Type::Bool,
+        }
+    );
+}
+
+#[test]
+fn test_higher_order_functions() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    twice(f, x) :<
+        return f(f(x))
+    >:
+
+    makeAdder(n) :<
+        add(x) :<
+            return (x + n)
+        >:
+        return add
+    >:
+
+    double(x) :<
+        return (x * 2)
+    >:
+
+    isOdd(x) :<
+        return ((x % 2) == 1)
+    >:
+
+    plus(a, b) :<
+        return (a + b)
+    >:
+
+    seen on a cob
+    remember(x) :<
+        nonlocal seen
+        seen assimilate x
+    >:
+
+    l on a cob
+    l assimilate 3
+    l assimilate 1
+    l assimilate 2
+
+    fns on a cob
+    fns assimilate double
+    fns assimilate upper
+
+    quadrupled squanch twice(double, 5)
+    eleven squanch makeAdder(10)(1)
+    shout squanch fns[1]("wubba")
+    doubled squanch map(l, double)
+    odds squanch filter(l, isOdd)
+    total squanch reduce(l, plus, 0)
+    each(l, remember)
+    fns[0](1)
+    byNegation squanch sort_by(l, makeAdder(-10))
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+
+    let get = |name: &str| state.get(name).unwrap().clone();
+    assert_eq!(get("quadrupled"), Value::new(20));
+    assert_eq!(get("eleven"), Value::new(11));
+    assert_eq!(get("shout"), Value::new("WUBBA"));
+    assert_eq!(get("doubled"), Value::new(vec![6, 2, 4]));
+    assert_eq!(get("odds"), Value::new(vec![3, 1]));
+    assert_eq!(get("total"), Value::new(6));
+    assert_eq!(get("seen"), Value::new(vec![3, 1, 2]));
+    assert_eq!(get("byNegation"), Value::new(vec![1, 2, 3]));
+}
+
+#[test]
+fn test_calling_non_functions() {
+    assert_eq!(
+        *run_err("l on a cob\nl assimilate 1\nx squanch l[0](2)").kind(),
+        EKind::UnexpectedType {
+            expected: value::Type::Function,
+            actual: value::Type::Int,
+        }
+    );
+    assert_eq!(
+        *run_err("f(x) :<\n show me what you got x\n>:\nx squanch map(split(\"ab\", \"\"), f)")
+            .kind(),
+        EKind::NoReturn("<anonymous>".into())
+    );
+}
+
+#[test]
+fn test_lambdas() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    l on a cob
+    l assimilate 1
+    l assimilate 2
+    l assimilate 3
+
+    offset squanch 10
+    shifted squanch map(l, meeseeks (x) -> (x + offset))
+    big squanch filter(l, meeseeks(x) :<
+        return (x more 1)
+    >:)
+
+    callbacks on a cob
+    callbacks assimilate meeseeks () -> "existence is pain"
+    pain squanch callbacks[0]()
+
+    counter squanch meeseeks () :<
+        nonlocal offset
+        offset squanch (offset + 1)
+        return offset
+    >:
+    counter()
+    counter()
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+
+    let get = |name: &str| state.get(name).unwrap().clone();
+    assert_eq!(get("shifted"), Value::new(vec![11, 12, 13]));
+    assert_eq!(get("big"), Value::new(vec![2, 3]));
+    assert_eq!(get("pain"), Value::new("existence is pain"));
+    assert_eq!(get("offset"), Value::new(12));
+}
+
+#[test]
+fn test_operands_are_read_before_calls_change_them() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    l squanch [1]
+    grow() :<
+        global l
+        l assimilate 2
+        return [1]
+    >:
+    same squanch l == grow()
+    first squanch l[0] + l squanch
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("same").unwrap(), Value::new(true));
+    assert_eq!(*state.get("l").unwrap(), Value::new(vec![1, 2]));
+    assert_eq!(*state.get("first").unwrap(), Value::new(3));
+}
+
+#[test]
+fn test_nested_indexing() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    grid(rows, cols) :<
+        m on a cob
+        while m squanch less rows :<
+            row on a cob
+            while row squanch less cols :<
+                row assimilate 0
+            >:
+            m assimilate row
+        >:
+        return m
+    >:
+
+    m squanch grid(2, 3)
+    m[1][2] squanch 7
+    m[0][0] squanch m[1][2] + 1
+    squanch m[1][0]
+    corner squanch grid(2, 2)[1][1]
+
+    family on a plumbus
+    family["smiths"] squanch grid(1, 1)
+    family["smiths"][0][0] squanch "jerry"
+    jerry squanch family["smiths"][0][0]
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(state.get("m").unwrap().to_string(), "[[8, 0, 0], [0, 7]]");
+    assert_eq!(*state.get("corner").unwrap(), Value::new(0));
+    assert_eq!(*state.get("jerry").unwrap(), Value::new("jerry"));
+
+    assert_eq!(
+        *run_err("m on a cob\nm assimilate 1\nm[0][0] squanch 2").kind(),
+        EKind::IndexUnindexable(value::Type::Int)
+    );
+    assert_eq!(
+        *run_err("m on a cob\nsquanch m[3][0]").kind(),
+        EKind::IndexOutOfBounds { len: 0, index: 3 }
+    );
+}
+
+#[test]
+fn test_list_literals() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    pair(a, b) :<
+        return [a, b]
+    >:
+
+    x squanch 2
+    l squanch [1, "two", rick, [x, x * 2],]
+    grid squanch [
+        pair(1, 2),
+        pair(3, 4),
+    ]
+    total squanch reduce([1, 2, 3], meeseeks (sum, n) -> sum + n, 0)
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(
+        *state.get("l").unwrap(),
+        Value::List(vec![
+            Value::new(1),
+            Value::new("two"),
+            Value::new(true),
+            Value::new(vec![2, 4]),
+        ])
+    );
+    assert_eq!(
+        *state.get("grid").unwrap(),
+        Value::List(vec![Value::new(vec![1, 2]), Value::new(vec![3, 4])])
+    );
+    assert_eq!(*state.get("total").unwrap(), Value::new(6));
+}
+
+#[test]
+fn test_for_loops() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    total squanch 0
+    l squanch [1, 2, 3]
+    for x in l :<
+        total squanch total + x
+        l assimilate x
+    >:
+
+    letters on a cob
+    for c in "rick" :<
+        letters assimilate upper(c)
+    >:
+
+    i squanch "kept"
+    squares on a cob
+    for i in 0 up to 4 :<
+        squares assimilate i * i
+    >:
+    for i in 5 up to 0 :<
+        squares assimilate i
+    >:
+
+    first_big(l) :<
+        for x in l :<
+            if x more 10 :<
+                return x
+            >:
+        >:
+        return -1
+    >:
+    big squanch first_big([3, 30, 300])
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("total").unwrap(), Value::new(6));
+    assert_eq!(*state.get("l").unwrap(), Value::new(vec![1, 2, 3, 1, 2, 3]));
+    assert_eq!(
+        *state.get("letters").unwrap(),
+        Value::new(vec!["R", "I", "C", "K"])
+    );
+    assert_eq!(*state.get("squares").unwrap(), Value::new(vec![0, 1, 4, 9]));
+    assert_eq!(*state.get("i").unwrap(), Value::new("kept"));
+    assert!(state.get("c").is_err());
+    assert_eq!(*state.get("big").unwrap(), Value::new(30));
+
+    assert_eq!(
+        *run_err("for x in 10 :< >:").kind(),
+        EKind::UnexpectedType {
+            expected: value::Type::Union(Box::new(value::Type::List), Box::new(value::Type::Str)),
+            actual: value::Type::Int,
+        }
+    );
+    assert_eq!(
+        *run_err("for x in 0 up to \"ten\" :< >:").kind(),
+        EKind::UnexpectedType {
+            expected: value::Type::Int,
+            actual: value::Type::Str,
+        }
+    );
+}
+
+#[test]
+fn test_break_continue() {
+    let mut state = State::new();
+
+    let code = grammar::file(
+        r#"
+    odds on a cob
+    for i in 0 up to 10 :<
+        if i % 2 == 0 :<
+            continue
+        >:
+        if i more 6 :<
+            break
+        >:
+        odds assimilate i
+    >:
+
+    pairs on a cob
+    outer: for i in 0 up to 3 :<
+        for j in 0 up to 3 :<
+            if j == 2 :<
+                continue outer
+            >:
+            if i == 2 :<
+                break outer
+            >:
+            pairs assimilate str(i) + str(j)
+        >:
+    >:
+
+    n squanch 0
+    while rick :<
+        normal plan :<
+            n squanch n + 1
+            if n == 3 :<
+                break
+            >:
+        >: plan for failure :<
+        >:
+    >:
+    "#,
+    )
+    .unwrap();
+
+    state.run(&code).unwrap();
+    assert_eq!(*state.get("odds").unwrap(), Value::new(vec![1, 3, 5]));
+    assert_eq!(
+        *state.get("pairs").unwrap(),
+        Value::new(vec!["00", "01", "10", "11"])
+    );
+    assert!(state.get("i").is_err());
+    assert_eq!(*state.get("n").unwrap(), Value::new(3));
+
+    assert_eq!(*run_err("break").kind(), EKind::OutsideLoop("break".into()));
+    assert_eq!(
+        *run_err("for i in [1] :<\n    continue inner\n>:").kind(),
+        EKind::UnknownLabel("inner".into())
+    );
+    assert_eq!(
+        *run_err("stop() :<\n    continue\n>:\nwhile rick :<\n    stop()\n>:").kind(),
+        EKind::OutsideLoop("continue".into())
+    );
+}
@@ -1,4 +1,4 @@
-use crate::expression::Expression;
+use crate::{expression::Expression, span::Span, symbols::Ident};
 
 use std::{
     cmp,
@@ -8,36 +8,63 @@ use std::{
 
 #[derive(Debug, Clone)]
 pub struct Statement {
-    start: usize,
-    end: usize,
+    span: Span,
     pub kind: StatementKind,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum StatementKind {
-    Assignment(String, Expression),
-    Delete(String),
+    Assignment(Ident, Expression),
+    Delete(Ident),
     Print(Expression),
     PrintNoNl(Expression),
-    ListNew(String),
-    ListAppend(String, Expression),
-    ListAssign(String, Expression, Expression),
-    ListDelete(String, Expression),
+    ListNew(Ident),
+    MapNew(Ident),
+    ListAppend(Ident, Expression),
+    ListAssign(Ident, Vec<Expression>, Expression),
+    ListDelete(Ident, Vec<Expression>),
     If(Expression, Vec<Statement>, Option<Vec<Statement>>),
     While(Expression, Vec<Statement>),
-    Input(String),
-    Catch(Vec<Statement>, Vec<Statement>),
-    Function(String, Vec<String>, Vec<Statement>),
+    For(Ident, Iterable, Vec<Statement>),
+    Labeled(Ident, Box<Statement>),
+    Break(Option<Ident>),
+    Continue(Option<Ident>),
+    Input(Ident),
+    Catch(Vec<Statement>, Option<Ident>, Vec<Statement>),
+    Rethrow(Expression),
+    Throw(Expression),
+    Function(Ident, Vec<Ident>, Vec<Statement>),
+    Global(Vec<Ident>),
+    Nonlocal(Vec<Ident>),
     Return(Expression),
-    FunctionCall(String, Vec<Expression>),
+    FunctionCall(Ident, Vec<Expression>),
+    Call(Expression),
     DylibLoad(String, Vec<Statement>),
+    Import(String, Imports),
+}
+
+/// What a `for` loop walks over.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Iterable {
+    /// The items of a list or the characters of a string.
+    Each(Expression),
+    /// Every int from the first expression up to, but not including, the second.
+    Range(Expression, Expression),
+}
+
+/// What an `interdimensional cable` import brings into scope.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Imports {
+    /// The whole module as a map, named after the file unless given a name.
+    Module(Option<Ident>),
+    Names(Vec<Ident>),
 }
 
 #[cfg(test)]
 impl StatementKind {
     pub fn assignment<S, E>(name: S, expr: E) -> Self
     where
-        S: Into<String>,
+        S: Into<Ident>,
         E: Into<Expression>,
     {
         StatementKind::Assignment(name.into(), expr.into())
@@ -59,7 +86,7 @@ impl StatementKind {
 
     pub fn list_append<S, E>(name: S, expr: E) -> Self
     where
-        S: Into<String>,
+        S: Into<Ident>,
         E: Into<Expression>,
     {
         StatementKind::ListAppend(name.into(), expr.into())
@@ -67,10 +94,10 @@ impl StatementKind {
 
     pub fn list_delete<S, E>(name: S, expr: E) -> Self
     where
-        S: Into<String>,
+        S: Into<Ident>,
         E: Into<Expression>,
     {
-        StatementKind::ListDelete(name.into(), expr.into())
+        StatementKind::ListDelete(name.into(), vec![expr.into()])
     }
 
     pub fn if_block<E>(
@@ -91,10 +118,27 @@ impl StatementKind {
         StatementKind::While(condition.into(), body)
     }
 
+    pub fn for_each<S, E>(name: S, iterable: E, body: Vec<Statement>) -> Self
+    where
+        S: Into<Ident>,
+        E: Into<Expression>,
+    {
+        StatementKind::For(name.into(), Iterable::Each(iterable.into()), body)
+    }
+
+    pub fn for_range<S, E, R>(name: S, start: E, end: R, body: Vec<Statement>) -> Self
+    where
+        S: Into<Ident>,
+        E: Into<Expression>,
+        R: Into<Expression>,
+    {
+        StatementKind::For(name.into(), Iterable::Range(start.into(), end.into()), body)
+    }
+
     pub fn function<Name, Args, Body>(name: Name, args: Vec<Args>, body: Vec<Body>) -> Self
     where
-        Name: Into<String>,
-        Args: Into<String>,
+        Name: Into<Ident>,
+        Args: Into<Ident>,
         Body: Into<Statement>,
     {
         StatementKind::Function(
@@ -104,29 +148,64 @@ impl StatementKind {
         )
     }
 
+    pub fn global<S>(names: Vec<S>) -> Self
+    where
+        S: Into<Ident>,
+    {
+        StatementKind::Global(names.into_iter().map(|x| x.into()).collect())
+    }
+
+    pub fn nonlocal<S>(names: Vec<S>) -> Self
+    where
+        S: Into<Ident>,
+    {
+        StatementKind::Nonlocal(names.into_iter().map(|x| x.into()).collect())
+    }
+
     pub fn input<S>(name: S) -> Self
     where
-        S: Into<String>,
+        S: Into<Ident>,
     {
         StatementKind::Input(name.into())
     }
 
     pub fn catch(try_block: Vec<Statement>, catch: Vec<Statement>) -> Self {
-        StatementKind::Catch(try_block, catch)
+        StatementKind::Catch(try_block, None, catch)
+    }
+
+    pub fn catch_as<S>(try_block: Vec<Statement>, name: S, catch: Vec<Statement>) -> Self
+    where
+        S: Into<Ident>,
+    {
+        StatementKind::Catch(try_block, Some(name.into()), catch)
+    }
+
+    pub fn rethrow<E>(expr: E) -> Self
+    where
+        E: Into<Expression>,
+    {
+        StatementKind::Rethrow(expr.into())
+    }
+
+    pub fn throw<E>(expr: E) -> Self
+    where
+        E: Into<Expression>,
+    {
+        StatementKind::Throw(expr.into())
     }
 
     pub fn list_assign<S, E, R>(name: S, index: E, assign: R) -> Self
     where
-        S: Into<String>,
+        S: Into<Ident>,
         E: Into<Expression>,
         R: Into<Expression>,
     {
-        StatementKind::ListAssign(name.into(), index.into(), assign.into())
+        StatementKind::ListAssign(name.into(), vec![index.into()], assign.into())
     }
 
     pub fn delete<S>(name: S) -> Self
     where
-        S: Into<String>,
+        S: Into<Ident>,
     {
         StatementKind::Delete(name.into())
     }
@@ -140,34 +219,51 @@ impl StatementKind {
 
     pub fn new_list<S>(name: S) -> Self
     where
-        S: Into<String>,
+        S: Into<Ident>,
     {
         StatementKind::ListNew(name.into())
     }
+
+    pub fn new_map<S>(name: S) -> Self
+    where
+        S: Into<Ident>,
+    {
+        StatementKind::MapNew(name.into())
+    }
+
+    pub fn import<S>(path: S, imports: Imports) -> Self
+    where
+        S: Into<String>,
+    {
+        StatementKind::Import(path.into(), imports)
+    }
 }
 
 impl Statement {
-    pub fn new(kind: StatementKind, start: usize, end: usize) -> Self {
-        Self { kind, start, end }
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Self { kind, span }
     }
 
     #[cfg(test)]
     pub fn tnew(kind: StatementKind) -> Self {
         Self {
             kind,
-            start: 0,
-            end: 0,
+            span: Span::default(),
         }
     }
 
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
     pub fn get_source(&self, filename: &str) -> io::Result<String> {
         let mut source = String::new();
         let mut f = File::open(filename)?;
         f.read_to_string(&mut source)?;
 
-        assert!(self.start < self.end);
+        assert!(self.span.len > 0);
 
-        Ok(source[self.start..self.end].to_string())
+        Ok(source[self.span.start..self.span.end()].to_string())
     }
 }
 
@@ -186,6 +282,6 @@ impl cmp::PartialEq<Statement> for Statement {
 
     #[cfg(not(test))]
     fn eq(&self, other: &Self) -> bool {
-        self.kind == other.kind && self.start == other.start && self.end == other.end
+        self.kind == other.kind && self.span == other.span
     }
 }
@@ -0,0 +1,119 @@
+use lazy_static::*;
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasherDefault, Hasher},
+    sync::Mutex,
+};
+
+#[cfg(test)]
+mod test;
+
+lazy_static! {
+    static ref INTERNER: Mutex<Interner> = Mutex::new(Interner::default());
+}
+
+#[derive(Default)]
+struct Interner {
+    ids: HashMap<&'static str, Ident>,
+    names: Vec<&'static str>,
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Ident {
+        if let Some(&ident) = self.ids.get(name) {
+            return ident;
+        }
+
+        let name: &'static str = Box::leak(name.to_string().into_boxed_str());
+        let ident = Ident(self.names.len() as u32);
+
+        self.names.push(name);
+        self.ids.insert(name, ident);
+        ident
+    }
+}
+
+/// An interned identifier. Comparing and hashing is done on the id alone, the
+/// name is only looked up for display and errors.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Ident(u32);
+
+impl Ident {
+    pub fn intern(name: &str) -> Self {
+        INTERNER.lock().unwrap().intern(name)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        INTERNER.lock().unwrap().names[self.0 as usize]
+    }
+}
+
+impl From<&str> for Ident {
+    fn from(name: &str) -> Self {
+        Self::intern(name)
+    }
+}
+
+impl From<&String> for Ident {
+    fn from(name: &String) -> Self {
+        Self::intern(name)
+    }
+}
+
+impl From<String> for Ident {
+    fn from(name: String) -> Self {
+        Self::intern(&name)
+    }
+}
+
+impl PartialEq<str> for Ident {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Ident {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Idents are small dense integers, so a multiplicative hash spreads them well
+// enough without paying for SipHash on every variable access.
+#[derive(Default)]
+pub struct IdentHasher(u64);
+
+impl Hasher for IdentHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(u64::from(byte));
+        }
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write_u64(u64::from(i));
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = (self.0.rotate_left(5) ^ i).wrapping_mul(0x517c_c1b7_2722_0a95);
+    }
+}
+
+pub type SymbolTable<V> = HashMap<Ident, V, BuildHasherDefault<IdentHasher>>;
@@ -0,0 +1,26 @@
+use crate::symbols::{Ident, SymbolTable};
+
+#[test]
+fn should_intern_to_the_same_ident() {
+    assert_eq!(Ident::intern("rick"), Ident::intern("rick"));
+    assert_ne!(Ident::intern("rick"), Ident::intern("morty"));
+}
+
+#[test]
+fn should_keep_the_name() {
+    let ident = Ident::intern("squanchy");
+
+    assert_eq!(ident.as_str(), "squanchy");
+    assert_eq!(ident.to_string(), "squanchy");
+    assert_eq!(format!("{:?}", ident), "\"squanchy\"");
+}
+
+#[test]
+fn should_store_and_remove_by_ident() {
+    let mut table: SymbolTable<i32> = SymbolTable::default();
+    table.insert("x".into(), 4);
+
+    assert_eq!(table.get(&Ident::intern("x")), Some(&4));
+    assert_eq!(table.remove(&"x".into()), Some(4));
+    assert_eq!(table.get(&Ident::intern("x")), None);
+}
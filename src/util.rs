@@ -1,4 +1,4 @@
-use crate::value::Value;
+use crate::value::{Map, Value};
 use std::fmt;
 
 pub fn slice_format<T>(x: &[T]) -> String
@@ -24,7 +24,7 @@ pub fn slice_value_format(x: &[Value]) -> String {
     for (idx, val) in x.iter().enumerate() {
         if let Value::Str(ref str) = *val {
             s.push('"');
-            s.push_str(&str);
+            s.push_str(str);
             s.push('"');
         } else {
             s.push_str(&format!("{}", val));
@@ -37,3 +37,24 @@ pub fn slice_value_format(x: &[Value]) -> String {
 
     s
 }
+
+pub fn map_format(x: &Map) -> String {
+    let mut s: String = "{".into();
+
+    for (idx, (key, val)) in x.iter().enumerate() {
+        s.push_str(&format!("{}: ", key));
+        if let Value::Str(ref str) = *val {
+            s.push('"');
+            s.push_str(str);
+            s.push('"');
+        } else {
+            s.push_str(&format!("{}", val));
+        }
+        if idx != x.len() - 1 {
+            s.push_str(", ");
+        }
+    }
+    s.push('}');
+
+    s
+}
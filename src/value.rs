@@ -1,14 +1,30 @@
 use crate::{
-    error::{ErrorKind, SwResult},
+    builtins::Builtin,
+    error::{ErrorKind, ErrorValue, SwResult},
+    plugin,
+    state::{Scope, State},
     statement::Statement,
-    util, Operator,
+    symbols::Ident,
+    util, vm, Operator,
 };
 use lazy_static::*;
 use regex::Regex;
-use std::{clone, cmp::Ordering, f64 as FloatValueType, fmt};
+use std::{
+    borrow::Cow,
+    cell::OnceCell,
+    clone,
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt,
+    io::{self, Write},
+    ops::Range,
+    rc::Rc,
+    vec,
+};
 
 pub type FloatT = f64;
 pub type IntT = i64;
+pub type Map = BTreeMap<Key, Value>;
 
 #[cfg(unix)]
 use libloading::os::unix::Symbol;
@@ -20,7 +36,30 @@ pub type _Func = unsafe extern "C" fn(*mut Vec<Value>) -> *mut SwResult<Value>;
 pub type _FuncSymbol = Symbol<_Func>;
 
 pub struct Func {
-    f: _FuncSymbol,
+    f: FuncAbi,
+}
+
+#[derive(Clone)]
+enum FuncAbi {
+    Builtin(&'static Builtin),
+    Legacy(_FuncSymbol),
+    C {
+        f: Symbol<plugin::SwFn>,
+        free: Symbol<plugin::SwFreeFn>,
+    },
+}
+
+pub struct Closure {
+    pub params: Vec<Ident>,
+    pub body: Vec<Statement>,
+    pub(crate) scopes: Vec<Scope>,
+    pub(crate) chunk: OnceCell<vm::Chunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Key {
+    Int(IntT),
+    Str(String),
 }
 
 #[derive(Debug, Clone)]
@@ -30,8 +69,44 @@ pub enum Value {
     Float(FloatT),
     Bool(bool),
     List(Vec<Value>),
-    Function(Vec<String>, Vec<Statement>),
+    Map(Map),
+    Function(Rc<Closure>),
     NativeFunction(Func),
+    Error(Rc<ErrorValue>),
+}
+
+/// The values a `for` loop hands out, one per iteration.
+#[derive(Debug)]
+pub(crate) enum Items {
+    List(vec::IntoIter<Value>),
+    Chars(vec::IntoIter<char>),
+    Range(Range<IntT>),
+}
+
+impl Iterator for Items {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        match self {
+            Items::List(items) => items.next(),
+            Items::Chars(chars) => chars.next().map(|c| Value::Str(c.to_string())),
+            Items::Range(range) => range.next().map(Value::Int),
+        }
+    }
+}
+
+impl Items {
+    /// Every int from `start` up to, but not including, `end`.
+    pub(crate) fn range(start: &Value, end: &Value) -> SwResult<Self> {
+        match (start, end) {
+            (&Value::Int(start), &Value::Int(end)) => Ok(Items::Range(start..end)),
+            (&Value::Int(_), other) | (other, _) => Err(ErrorKind::UnexpectedType {
+                expected: Type::Int,
+                actual: other.get_type(),
+            }
+            .into()),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -41,8 +116,10 @@ pub enum Type {
     Float,
     Bool,
     List,
+    Map,
     Function,
     NativeFunction,
+    Error,
     Union(Box<Self>, Box<Self>),
 }
 
@@ -53,9 +130,11 @@ impl fmt::Display for Type {
             Type::Int => write!(f, "int"),
             Type::Bool => write!(f, "bool"),
             Type::List => write!(f, "list"),
+            Type::Map => write!(f, "map"),
             Type::Float => write!(f, "float"),
             Type::Function => write!(f, "function"),
             Type::NativeFunction => write!(f, "native function"),
+            Type::Error => write!(f, "error"),
             Type::Union(t1, t2) => write!(f, "{} or {}", t1, t2),
         }
     }
@@ -71,8 +150,12 @@ impl fmt::Display for Value {
             Bool(x) if x => write!(f, "rick"),
             Bool(_) => write!(f, "morty"),
             List(ref x) => write!(f, "{}", util::slice_value_format(x)),
-            Function(ref params, _) => write!(f, "[Function {}]", util::slice_format(params)),
+            Map(ref x) => write!(f, "{}", util::map_format(x)),
+            Function(ref closure) => {
+                write!(f, "[Function {}]", util::slice_format(&closure.params))
+            }
             NativeFunction(_) => write!(f, "[Native Function]"),
+            Error(ref error) => write!(f, "{}: {}", error.kind, error.message),
         }
     }
 }
@@ -83,41 +166,85 @@ impl fmt::Debug for Func {
     }
 }
 
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[Function {}]", util::slice_format(&self.params))
+    }
+}
+
+impl Closure {
+    pub(crate) fn new(params: Vec<Ident>, body: Vec<Statement>, scopes: Vec<Scope>) -> Self {
+        Self {
+            params,
+            body,
+            scopes,
+            chunk: OnceCell::new(),
+        }
+    }
+}
+
 impl clone::Clone for Func {
     fn clone(&self) -> Self {
-        panic!("You cannot clone a native function.")
+        Self { f: self.f.clone() }
     }
 }
 
 impl Func {
     pub fn new(f: _FuncSymbol) -> Self {
-        Self { f }
+        Self {
+            f: FuncAbi::Legacy(f),
+        }
     }
 
-    pub fn call(&self, args: &mut Vec<Value>) -> SwResult<Value> {
-        let f = &self.f;
-        let val = unsafe {
-            let result = f(args as *mut Vec<Value>);
-            if result.is_null() {
-                return Err(ErrorKind::DylibReturnedNil.into());
-            }
+    pub fn c_abi(f: Symbol<plugin::SwFn>, free: Symbol<plugin::SwFreeFn>) -> Self {
+        Self {
+            f: FuncAbi::C { f, free },
+        }
+    }
 
-            Box::from_raw(result)
-        };
+    pub(crate) fn builtin(builtin: &'static Builtin) -> Self {
+        Self {
+            f: FuncAbi::Builtin(builtin),
+        }
+    }
 
-        *val
+    /// The builtin this is, if it is one.
+    pub(crate) fn builtin_name(&self) -> Option<&'static str> {
+        match self.f {
+            FuncAbi::Builtin(builtin) => Some(builtin.name),
+            _ => None,
+        }
+    }
+
+    pub fn call(&self, state: &State, args: &mut Vec<Value>) -> SwResult<Value> {
+        match self.f {
+            FuncAbi::Builtin(builtin) => builtin.call(state, std::mem::take(args)),
+            FuncAbi::Legacy(ref f) => {
+                let val = unsafe {
+                    let result = f(args as *mut Vec<Value>);
+                    if result.is_null() {
+                        return Err(ErrorKind::DylibReturnedNil.into());
+                    }
+
+                    Box::from_raw(result)
+                };
+
+                *val
+            }
+            FuncAbi::C { ref f, ref free } => unsafe { plugin::call(**f, **free, args) },
+        }
     }
 }
 
 impl From<_FuncSymbol> for Value {
     fn from(from: _FuncSymbol) -> Self {
-        Value::NativeFunction(Func { f: from })
+        Value::NativeFunction(Func::new(from))
     }
 }
 
 impl From<_FuncSymbol> for Func {
     fn from(from: _FuncSymbol) -> Self {
-        Self { f: from }
+        Self::new(from)
     }
 }
 
@@ -145,6 +272,12 @@ impl From<bool> for Value {
     }
 }
 
+impl From<Map> for Value {
+    fn from(from: Map) -> Self {
+        Value::Map(from)
+    }
+}
+
 impl From<String> for Value {
     fn from(from: String) -> Self {
         Value::Str(from)
@@ -187,8 +320,24 @@ impl Value {
             Float(_) => Type::Float,
             Bool(_) => Type::Bool,
             List(_) => Type::List,
-            Function(_, _) => Type::Function,
+            Map(_) => Type::Map,
+            Function(_) => Type::Function,
             NativeFunction(_) => Type::NativeFunction,
+            Error(_) => Type::Error,
+        }
+    }
+
+    /// The items of a list or the characters of a string. The loop walks
+    /// this copy, so changing the list while looping over it is harmless.
+    pub(crate) fn items(self) -> SwResult<Items> {
+        match self {
+            Value::List(l) => Ok(Items::List(l.into_iter())),
+            Value::Str(s) => Ok(Items::Chars(s.chars().collect::<Vec<_>>().into_iter())),
+            other => Err(ErrorKind::UnexpectedType {
+                expected: Type::Union(Box::new(Type::List), Box::new(Type::Str)),
+                actual: other.get_type(),
+            }
+            .into()),
         }
     }
 
@@ -205,11 +354,271 @@ impl Value {
         }
     }
 
+    pub fn index(&self, index: &Self) -> SwResult<Self> {
+        self.item(index).map(Cow::into_owned)
+    }
+
+    /// Like `index`, but borrows the items of lists and maps.
+    pub fn item(&self, index: &Self) -> SwResult<Cow<'_, Self>> {
+        match *self {
+            Value::List(ref l) => {
+                if let Value::Int(i) = *index {
+                    let index = i as usize;
+                    if index < l.len() {
+                        Ok(Cow::Borrowed(&l[index]))
+                    } else {
+                        Err(ErrorKind::IndexOutOfBounds {
+                            len: l.len(),
+                            index,
+                        }
+                        .into())
+                    }
+                } else {
+                    Err(ErrorKind::UnexpectedType {
+                        expected: Type::Int,
+                        actual: index.get_type(),
+                    }
+                    .into())
+                }
+            }
+            Value::Str(ref s) => {
+                if let Value::Int(i) = *index {
+                    let index = i as usize;
+                    let chars: Vec<char> = s.chars().collect();
+
+                    if index < chars.len() {
+                        Ok(Cow::Owned(Value::Str(chars[index].to_string())))
+                    } else {
+                        Err(ErrorKind::IndexOutOfBounds {
+                            len: chars.len(),
+                            index,
+                        }
+                        .into())
+                    }
+                } else {
+                    Err(ErrorKind::UnexpectedType {
+                        expected: Type::Int,
+                        actual: index.get_type(),
+                    }
+                    .into())
+                }
+            }
+            Value::Map(ref m) => {
+                let key = Key::from_value(index)?;
+                match m.get(&key) {
+                    Some(value) => Ok(Cow::Borrowed(value)),
+                    None => Err(ErrorKind::MissingKey(key.to_string()).into()),
+                }
+            }
+            Value::Error(ref error) => match *index {
+                Value::Str(ref field) => error.field(field).map(Cow::Owned),
+                _ => Err(ErrorKind::UnexpectedType {
+                    expected: Type::Str,
+                    actual: index.get_type(),
+                }
+                .into()),
+            },
+            _ => Err(ErrorKind::IndexUnindexable(self.get_type()).into()),
+        }
+    }
+
+    pub fn assign_index(&mut self, index: &Self, value: Self) -> SwResult<()> {
+        match *self {
+            Value::List(ref mut l) => {
+                let index = list_position(index)?;
+                let len = l.len();
+
+                if index < len {
+                    l[index] = value;
+                    Ok(())
+                } else {
+                    Err(ErrorKind::IndexOutOfBounds { len, index }.into())
+                }
+            }
+            Value::Map(ref mut m) => {
+                m.insert(Key::from_value(index)?, value);
+                Ok(())
+            }
+            _ => Err(ErrorKind::IndexUnindexable(self.get_type()).into()),
+        }
+    }
+
+    fn index_mut(&mut self, index: &Self) -> SwResult<&mut Self> {
+        match *self {
+            Value::List(ref mut l) => {
+                let index = list_position(index)?;
+                let len = l.len();
+
+                l.get_mut(index)
+                    .ok_or_else(|| ErrorKind::IndexOutOfBounds { len, index }.into())
+            }
+            Value::Map(ref mut m) => {
+                let key = Key::from_value(index)?;
+                match m.get_mut(&key) {
+                    Some(value) => Ok(value),
+                    None => Err(ErrorKind::MissingKey(key.to_string()).into()),
+                }
+            }
+            _ => Err(ErrorKind::IndexUnindexable(self.get_type()).into()),
+        }
+    }
+
+    /// Follows every index in `path`, so `m[i][j]` can be changed in place.
+    fn path_mut(&mut self, path: &[Self]) -> SwResult<&mut Self> {
+        let mut target = self;
+        for index in path {
+            target = target.index_mut(index)?;
+        }
+
+        Ok(target)
+    }
+
+    pub fn assign_nested(&mut self, path: &[Self], value: Self) -> SwResult<()> {
+        let (index, path) = path.split_last().expect("index paths are never empty");
+        self.path_mut(path)?.assign_index(index, value)
+    }
+
+    pub fn delete_nested(&mut self, path: &[Self]) -> SwResult<()> {
+        let (index, path) = path.split_last().expect("index paths are never empty");
+        self.path_mut(path)?.delete_index(index)
+    }
+
+    pub fn delete_index(&mut self, index: &Self) -> SwResult<()> {
+        match *self {
+            Value::List(ref mut l) => {
+                let index = list_position(index)?;
+                let len = l.len();
+
+                if index < len {
+                    l.remove(index);
+                    Ok(())
+                } else {
+                    Err(ErrorKind::IndexOutOfBounds { len, index }.into())
+                }
+            }
+            Value::Map(ref mut m) => {
+                let key = Key::from_value(index)?;
+                match m.remove(&key) {
+                    Some(_) => Ok(()),
+                    None => Err(ErrorKind::MissingKey(key.to_string()).into()),
+                }
+            }
+            _ => Err(ErrorKind::IndexUnindexable(self.get_type()).into()),
+        }
+    }
+
+    /// A total order over every value: bools, then numbers, strings, lists,
+    /// maps and errors. Functions come last and are all equal.
+    pub fn order(&self, other: &Self) -> Ordering {
+        use self::Value::*;
+        match (self, other) {
+            (Bool(b1), Bool(b2)) => b1.cmp(b2),
+            (Int(i1), Int(i2)) => i1.cmp(i2),
+            (Int(i), Float(f)) => order_int_float(*i, *f),
+            (Float(f), Int(i)) => order_int_float(*i, *f).reverse(),
+            (Float(f1), Float(f2)) => f1.partial_cmp(f2).unwrap_or_else(|| f1.total_cmp(f2)),
+            (Str(s1), Str(s2)) => s1.cmp(s2),
+            (List(l1), List(l2)) => {
+                order_seq(l1.iter().zip(l2), l1.len(), l2.len(), |(a, b)| a.order(b))
+            }
+            (Map(m1), Map(m2)) => order_seq(m1.iter().zip(m2), m1.len(), m2.len(), |(a, b)| {
+                a.0.cmp(b.0).then_with(|| a.1.order(b.1))
+            }),
+            (Error(e1), Error(e2)) => e1.message.cmp(&e2.message),
+            _ => self.order_rank().cmp(&other.order_rank()),
+        }
+    }
+
+    fn order_rank(&self) -> u8 {
+        use self::Value::*;
+        match *self {
+            Bool(_) => 0,
+            Int(_) | Float(_) => 1,
+            Str(_) => 2,
+            List(_) => 3,
+            Map(_) => 4,
+            Error(_) => 5,
+            Function(_) | NativeFunction(_) => 6,
+        }
+    }
+
+    fn as_list(&self) -> SwResult<&Vec<Self>> {
+        match *self {
+            Value::List(ref l) => Ok(l),
+            _ => Err(ErrorKind::UnexpectedType {
+                expected: Type::List,
+                actual: self.get_type(),
+            }
+            .into()),
+        }
+    }
+
+    pub fn sorted(&self) -> SwResult<Self> {
+        let mut list = self.as_list()?.clone();
+        list.sort_by(Self::order);
+        Ok(Value::List(list))
+    }
+
+    pub fn reversed(&self) -> SwResult<Self> {
+        Ok(Value::List(self.as_list()?.iter().rev().cloned().collect()))
+    }
+
+    /// The items from `start` up to, but not including, `end`.
+    pub fn slice(&self, start: &Self, end: &Self) -> SwResult<Self> {
+        let list = self.as_list()?;
+        let len = list.len();
+        let (start, end) = (list_position(start)?, list_position(end)?);
+
+        for &index in &[start, end] {
+            if index > len {
+                return Err(ErrorKind::IndexOutOfBounds { len, index }.into());
+            }
+        }
+
+        Ok(Value::List(list[start..end.max(start)].to_vec()))
+    }
+
+    pub fn insert(&mut self, index: &Self, value: Self) -> SwResult<()> {
+        let index = list_position(index)?;
+
+        match *self {
+            Value::List(ref mut l) if index <= l.len() => {
+                l.insert(index, value);
+                Ok(())
+            }
+            Value::List(ref l) => Err(ErrorKind::IndexOutOfBounds {
+                len: l.len(),
+                index,
+            }
+            .into()),
+            _ => self.as_list().map(|_| ()),
+        }
+    }
+
+    pub fn concat(&self, other: &Self) -> SwResult<Self> {
+        let mut list = self.as_list()?.clone();
+        list.extend(other.as_list()?.iter().cloned());
+        Ok(Value::List(list))
+    }
+
+    pub fn contains(&self, value: &Self) -> SwResult<bool> {
+        Ok(self.as_list()?.contains(value))
+    }
+
+    pub fn index_of(&self, value: &Self) -> SwResult<Option<usize>> {
+        Ok(self.as_list()?.iter().position(|item| item == value))
+    }
+
+    pub fn count(&self, value: &Self) -> SwResult<usize> {
+        Ok(self.as_list()?.iter().filter(|item| *item == value).count())
+    }
+
     pub fn is_empty(&self) -> SwResult<bool> {
         use self::Value::*;
         match *self {
             Str(ref s) => Ok(s.is_empty()),
             List(ref l) => Ok(l.is_empty()),
+            Map(ref m) => Ok(m.is_empty()),
             _ => Err(ErrorKind::UnexpectedType {
                 expected: Type::Union(Box::new(Type::Str), Box::new(Type::List)),
                 actual: self.get_type(),
@@ -219,18 +628,18 @@ impl Value {
     }
 
     #[cfg(feature = "debug_printing")]
-    pub fn print(&self) {
-        print!("{:?}", self);
+    pub fn print(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "{:?}", self)
     }
 
     #[cfg(not(feature = "debug_printing"))]
-    pub fn print(&self) {
-        print!("{}", self);
+    pub fn print(&self, out: &mut dyn Write) -> io::Result<()> {
+        write!(out, "{}", self)
     }
 
-    pub fn println(&self) {
-        self.print();
-        println!();
+    pub fn println(&self, out: &mut dyn Write) -> io::Result<()> {
+        self.print(out)?;
+        writeln!(out)
     }
 
     fn assert_f32(&self) -> SwResult<FloatT> {
@@ -452,16 +861,104 @@ impl PartialEq for Value {
             (Value::Bool(b1), Value::Bool(b2)) => b1 == b2,
             (Value::Str(ref s1), Value::Str(ref s2)) => s1 == s2,
             (Value::List(ref l1), Value::List(ref l2)) => l1 == l2,
+            (Value::Map(ref m1), Value::Map(ref m2)) => m1 == m2,
             (Value::Int(i1), Value::Int(i2)) => i1 == i2,
             (Value::Int(i), Value::Float(f)) | (Value::Float(f), Value::Int(i)) => {
-                (*i as FloatT - f).abs() < FloatValueType::EPSILON
+                (*i as FloatT - f).abs() < FloatT::EPSILON
             }
-            (Value::Float(f1), Value::Float(f2)) => (f1 - f2).abs() < FloatValueType::EPSILON,
+            (Value::Float(f1), Value::Float(f2)) => (f1 - f2).abs() < FloatT::EPSILON,
+            (Value::Error(ref e1), Value::Error(ref e2)) => e1 == e2,
             _ => false,
         }
     }
 }
 
+impl Key {
+    pub fn from_value(value: &Value) -> SwResult<Self> {
+        match *value {
+            Value::Int(i) => Ok(Key::Int(i)),
+            Value::Str(ref s) => Ok(Key::Str(s.clone())),
+            _ => Err(ErrorKind::UnexpectedType {
+                expected: Type::Union(Box::new(Type::Str), Box::new(Type::Int)),
+                actual: value.get_type(),
+            }
+            .into()),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Key::Int(i) => write!(f, "{}", i),
+            Key::Str(s) => write!(f, "\"{}\"", s),
+        }
+    }
+}
+
+impl From<Key> for Value {
+    fn from(from: Key) -> Self {
+        match from {
+            Key::Int(i) => Value::Int(i),
+            Key::Str(s) => Value::Str(s),
+        }
+    }
+}
+
+/// Compares an int with a float exactly, since casting large ints to floats
+/// rounds them. NaNs go below or above every number depending on their sign.
+fn order_int_float(i: IntT, f: FloatT) -> Ordering {
+    // 2^63, the first float past the end of the int range.
+    const INT_END: FloatT = 9_223_372_036_854_775_808.0;
+
+    if f.is_nan() {
+        return if f.is_sign_negative() {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+    }
+    if f >= INT_END {
+        return Ordering::Less;
+    }
+    if f < -INT_END {
+        return Ordering::Greater;
+    }
+
+    let whole = f.trunc();
+    i.cmp(&(whole as IntT)).then_with(|| {
+        if f > whole {
+            Ordering::Less
+        } else if f < whole {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    })
+}
+
+fn order_seq<I, F>(pairs: I, len1: usize, len2: usize, order: F) -> Ordering
+where
+    I: Iterator,
+    F: FnMut(I::Item) -> Ordering,
+{
+    pairs
+        .map(order)
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or_else(|| len1.cmp(&len2))
+}
+
+fn list_position(index: &Value) -> SwResult<usize> {
+    match *index {
+        Value::Int(i) => Ok(i as usize),
+        _ => Err(ErrorKind::UnexpectedType {
+            expected: Type::Int,
+            actual: index.get_type(),
+        }
+        .into()),
+    }
+}
+
 pub fn string_parse(string: &str) -> String {
     lazy_static! {
         static ref NEWLINE: Regex = Regex::new("[^\\\\]?\\\\n").unwrap();
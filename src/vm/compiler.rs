@@ -0,0 +1,620 @@
+use super::{Chunk, Op, Operand, Var};
+use crate::{
+    expression::{Expression, ExpressionKind},
+    span::Span,
+    statement::{Iterable, Statement, StatementKind},
+    symbols::Ident,
+    value::{Map, Value},
+};
+
+pub struct Compiler {
+    chunk: Chunk,
+    place: usize,
+    span: Span,
+    slots: bool,
+    // How many of the chunk's locals were collected up front. The slots after
+    // them belong to for loop variables, which are only in reach inside their
+    // loop.
+    named: usize,
+    loop_slots: Vec<(Ident, usize)>,
+    control: Vec<Control>,
+    label: Option<Ident>,
+}
+
+/// The blocks a `break` or `continue` might have to jump out of.
+enum Control {
+    Loop {
+        label: Option<Ident>,
+        iter: bool,
+        scope: bool,
+        continue_to: usize,
+        breaks: Vec<usize>,
+    },
+    Try,
+}
+
+impl Compiler {
+    fn new(locals: Vec<Ident>, slots: bool) -> Self {
+        let named = locals.len();
+
+        Self {
+            chunk: Chunk {
+                ops: Vec::new(),
+                places: Vec::new(),
+                spans: Vec::new(),
+                statements: Vec::new(),
+                constants: Vec::new(),
+                names: Vec::new(),
+                functions: Vec::new(),
+                locals,
+                reads: Vec::new(),
+                slots,
+            },
+            place: 0,
+            span: Span::default(),
+            slots,
+            named,
+            loop_slots: Vec::new(),
+            control: Vec::new(),
+            label: None,
+        }
+    }
+
+    pub fn compile(statements: &[Statement]) -> Chunk {
+        let mut compiler = Self::new(Vec::new(), false);
+        compiler.block(statements);
+        compiler.chunk
+    }
+
+    pub fn compile_function(params: &[Ident], body: &[Statement]) -> Chunk {
+        if is_dynamic(body) {
+            let mut compiler = Self::new(params.to_vec(), false);
+            compiler.block(body);
+            return compiler.chunk;
+        }
+
+        let mut locals = params.to_vec();
+        collect_locals(body, &mut locals);
+
+        let mut compiler = Self::new(locals, true);
+        compiler.block(body);
+        compiler.chunk
+    }
+
+    fn emit(&mut self, op: Op) -> usize {
+        self.chunk.ops.push(op);
+        self.chunk.places.push(self.place);
+        self.chunk.spans.push(self.span);
+        self.chunk.ops.len() - 1
+    }
+
+    fn here(&self) -> usize {
+        self.chunk.ops.len()
+    }
+
+    fn patch(&mut self, at: usize) {
+        let target = self.here();
+        match self.chunk.ops[at] {
+            Op::Jump(ref mut t)
+            | Op::JumpIfFalse(ref mut t)
+            | Op::Next(ref mut t)
+            | Op::PushHandler(ref mut t) => *t = target,
+            ref op => panic!("Tried to patch a jump target into {:?}", op),
+        }
+    }
+
+    fn constant(&mut self, value: Value) -> usize {
+        self.chunk.constants.push(value);
+        self.chunk.constants.len() - 1
+    }
+
+    fn name(&mut self, name: Ident) -> usize {
+        match self.chunk.names.iter().position(|&n| n == name) {
+            Some(idx) => idx,
+            None => {
+                self.chunk.names.push(name);
+                self.chunk.names.len() - 1
+            }
+        }
+    }
+
+    fn var(&mut self, name: Ident) -> Var {
+        if self.slots {
+            if let Some(&(_, slot)) = self.loop_slots.iter().rev().find(|&&(n, _)| n == name) {
+                return Var::Local(slot);
+            }
+
+            let named = &self.chunk.locals[..self.named];
+            if let Some(slot) = named.iter().position(|&n| n == name) {
+                return Var::Local(slot);
+            }
+        }
+
+        Var::Name(self.name(name))
+    }
+
+    fn block(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        let outer_place = self.place;
+        let outer_span = self.span;
+        let label = self.label.take();
+        self.chunk.statements.push(statement.clone());
+        self.place = self.chunk.statements.len() - 1;
+        self.span = statement.span();
+
+        match statement.kind {
+            StatementKind::Assignment(name, ref exp) => {
+                self.expression(exp);
+                let var = self.var(name);
+                self.emit(Op::Store(var));
+            }
+            StatementKind::Delete(name) => {
+                let var = self.var(name);
+                self.emit(Op::Delete(var));
+            }
+            StatementKind::Print(ref exp) => {
+                self.expression(exp);
+                self.emit(Op::Print);
+            }
+            StatementKind::PrintNoNl(ref exp) => {
+                self.expression(exp);
+                self.emit(Op::PrintNoNl);
+            }
+            StatementKind::ListNew(name) => {
+                let idx = self.constant(Value::List(Vec::new()));
+                self.emit(Op::Constant(idx));
+                let var = self.var(name);
+                self.emit(Op::Store(var));
+            }
+            StatementKind::MapNew(name) => {
+                let idx = self.constant(Value::Map(Map::new()));
+                self.emit(Op::Constant(idx));
+                let var = self.var(name);
+                self.emit(Op::Store(var));
+            }
+            StatementKind::ListAppend(name, ref exp) => {
+                self.expression(exp);
+                let var = self.var(name);
+                self.emit(Op::Append(var));
+            }
+            StatementKind::ListAssign(name, ref indexes, ref exp) => {
+                self.expression(exp);
+                for index in indexes {
+                    self.expression(index);
+                }
+                let var = self.var(name);
+                self.emit(Op::AssignIndex(var, indexes.len()));
+            }
+            StatementKind::ListDelete(name, ref indexes) => {
+                for index in indexes {
+                    self.expression(index);
+                }
+                let var = self.var(name);
+                self.emit(Op::DeleteIndex(var, indexes.len()));
+            }
+            StatementKind::If(ref condition, ref if_body, ref else_body) => {
+                self.expression(condition);
+                let to_else = self.emit(Op::JumpIfFalse(0));
+                self.block(if_body);
+
+                match *else_body {
+                    Some(ref else_body) => {
+                        let to_end = self.emit(Op::Jump(0));
+                        self.patch(to_else);
+                        self.block(else_body);
+                        self.patch(to_end);
+                    }
+                    None => self.patch(to_else),
+                }
+            }
+            StatementKind::While(ref condition, ref body) => {
+                let start = self.here();
+                self.expression(condition);
+                let to_end = self.emit(Op::JumpIfFalse(0));
+                self.loop_body(label, false, start, body);
+                self.emit(Op::Jump(start));
+                self.patch(to_end);
+                self.end_loop();
+            }
+            StatementKind::For(name, ref iterable, ref body) => {
+                match *iterable {
+                    Iterable::Each(ref exp) => {
+                        self.expression(exp);
+                        self.emit(Op::Iter);
+                    }
+                    Iterable::Range(ref start, ref end) => {
+                        self.expression(start);
+                        self.expression(end);
+                        self.emit(Op::IterRange);
+                    }
+                }
+
+                // The loop variable gets a fresh slot, or a scope of its own
+                // when locals live in the scope chain.
+                let var = if self.slots {
+                    let slot = self.chunk.locals.len();
+                    self.chunk.locals.push(name);
+                    self.loop_slots.push((name, slot));
+                    Var::Local(slot)
+                } else {
+                    let idx = self.name(name);
+                    self.emit(Op::PushScope(idx));
+                    Var::Name(idx)
+                };
+
+                let start = self.emit(Op::Next(0));
+                self.emit(Op::Store(var));
+                self.loop_body(label, true, start, body);
+                self.emit(Op::Jump(start));
+                self.patch(start);
+                self.end_loop();
+
+                if self.slots {
+                    self.loop_slots.pop();
+                } else {
+                    self.emit(Op::PopScope);
+                }
+            }
+            StatementKind::Labeled(label, ref inner) => {
+                self.label = Some(label);
+                self.statement(inner);
+            }
+            StatementKind::Break(label) => self.jump_out(label, true),
+            StatementKind::Continue(label) => self.jump_out(label, false),
+            StatementKind::Input(name) => {
+                let var = self.var(name);
+                self.emit(Op::Input(var));
+            }
+            StatementKind::Catch(ref try_block, name, ref catch) => {
+                let handler = self.emit(Op::PushHandler(0));
+                self.control.push(Control::Try);
+                self.block(try_block);
+                self.control.pop();
+                self.emit(Op::PopHandler);
+                let to_end = self.emit(Op::Jump(0));
+
+                // The vm pushes the caught error before jumping to the handler.
+                self.patch(handler);
+                match name {
+                    Some(name) => {
+                        let var = self.var(name);
+                        self.emit(Op::Store(var));
+                    }
+                    None => {
+                        self.emit(Op::Pop);
+                    }
+                }
+
+                self.block(catch);
+                self.patch(to_end);
+            }
+            StatementKind::Rethrow(ref exp) => {
+                self.expression(exp);
+                self.emit(Op::Rethrow);
+            }
+            StatementKind::Throw(ref exp) => {
+                self.expression(exp);
+                self.emit(Op::Throw);
+            }
+            StatementKind::Function(name, ref params, ref body) => {
+                self.chunk.functions.push((params.clone(), body.clone()));
+                let idx = self.chunk.functions.len() - 1;
+                self.emit(Op::MakeClosure(idx));
+                let var = self.var(name);
+                self.emit(Op::Store(var));
+            }
+            StatementKind::Return(ref exp) => {
+                self.expression(exp);
+                self.emit(Op::Return);
+            }
+            StatementKind::FunctionCall(name, ref args) => {
+                self.call(name, args);
+                self.emit(Op::Pop);
+            }
+            StatementKind::Call(ref call) => {
+                self.expression(call);
+                self.emit(Op::Pop);
+            }
+            StatementKind::Global(_)
+            | StatementKind::Nonlocal(_)
+            | StatementKind::DylibLoad(_, _)
+            | StatementKind::Import(_, _) => {
+                self.emit(Op::Exec(self.place));
+            }
+        }
+
+        self.place = outer_place;
+        self.span = outer_span;
+    }
+
+    fn loop_body(&mut self, label: Option<Ident>, iter: bool, start: usize, body: &[Statement]) {
+        self.control.push(Control::Loop {
+            label,
+            iter,
+            scope: iter && !self.slots,
+            continue_to: start,
+            breaks: Vec::new(),
+        });
+        self.block(body);
+    }
+
+    fn end_loop(&mut self) {
+        if let Some(Control::Loop { breaks, .. }) = self.control.pop() {
+            for at in breaks {
+                self.patch(at);
+            }
+        }
+    }
+
+    // Leaves every handler and for loop iterator between here and the target
+    // loop behind before jumping. Without a target the walker raises the error.
+    fn jump_out(&mut self, label: Option<Ident>, is_break: bool) {
+        let target = self.control.iter().rposition(|control| match *control {
+            Control::Loop { label: l, .. } => label.is_none() || l == label,
+            Control::Try => false,
+        });
+
+        let target = match target {
+            Some(target) => target,
+            None => {
+                self.emit(Op::Exec(self.place));
+                return;
+            }
+        };
+
+        let mut unwind = Vec::new();
+        for control in &self.control[target + 1..] {
+            match *control {
+                Control::Loop {
+                    iter: true, scope, ..
+                } => {
+                    unwind.push(Op::PopIter);
+                    if scope {
+                        unwind.push(Op::PopScope);
+                    }
+                }
+                Control::Loop { .. } => {}
+                Control::Try => unwind.push(Op::PopHandler),
+            }
+        }
+
+        for op in unwind {
+            self.emit(op);
+        }
+
+        let (iter, continue_to) = match self.control[target] {
+            Control::Loop {
+                iter, continue_to, ..
+            } => (iter, continue_to),
+            Control::Try => unreachable!(),
+        };
+
+        if !is_break {
+            self.emit(Op::Jump(continue_to));
+            return;
+        }
+
+        // The target's scope is popped where its breaks land.
+        if iter {
+            self.emit(Op::PopIter);
+        }
+
+        let jump = self.emit(Op::Jump(0));
+        if let Control::Loop { ref mut breaks, .. } = self.control[target] {
+            breaks.push(jump);
+        }
+    }
+
+    fn call(&mut self, name: Ident, args: &[Expression]) {
+        for arg in args {
+            self.expression(arg);
+        }
+
+        let var = self.var(name);
+        self.emit(Op::Call(var, args.len()));
+    }
+
+    // Right operands that are constants or variables don't need to go through
+    // the stack. They are read last either way.
+    fn operand(&mut self, expression: &Expression) -> Operand {
+        match expression.kind {
+            ExpressionKind::Value(ref value) => Operand::Constant(self.constant(value.clone())),
+            ExpressionKind::Variable(name) => {
+                let at = self.here();
+                self.chunk.reads.push((at, expression.span()));
+                Operand::Var(self.var(name))
+            }
+            _ => {
+                self.expression(expression);
+                Operand::Stack
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        let outer_span = self.span;
+        self.span = expression.span();
+
+        match expression.kind {
+            ExpressionKind::Variable(name) => {
+                let var = self.var(name);
+                self.emit(Op::Load(var));
+            }
+            ExpressionKind::OpExp(ref left, operator, ref right) => {
+                self.expression(left);
+                let operand = self.operand(right);
+                self.emit(Op::Binary(operator, operand));
+            }
+            ExpressionKind::Value(ref value) => {
+                let idx = self.constant(value.clone());
+                self.emit(Op::Constant(idx));
+            }
+            ExpressionKind::ListIndex(ref target, ref index) => match target.kind {
+                ExpressionKind::Variable(name) => {
+                    self.expression(index);
+                    let var = self.var(name);
+                    self.emit(Op::Index(var));
+                }
+                _ => {
+                    self.expression(target);
+                    self.expression(index);
+                    self.emit(Op::IndexValue);
+                }
+            },
+            ExpressionKind::ListLength(name) => {
+                let var = self.var(name);
+                self.emit(Op::Length(var));
+            }
+            ExpressionKind::Not(ref exp) => {
+                self.expression(exp);
+                self.emit(Op::Not);
+            }
+            ExpressionKind::Eval(ref exp) => {
+                self.expression(exp);
+                self.emit(Op::Eval);
+            }
+            ExpressionKind::FunctionCall(name, ref args) => self.call(name, args),
+            ExpressionKind::Lambda(ref params, ref body) => {
+                self.chunk.functions.push((params.clone(), body.clone()));
+                let idx = self.chunk.functions.len() - 1;
+                self.emit(Op::MakeClosure(idx));
+            }
+            ExpressionKind::List(ref items) => {
+                for item in items {
+                    self.expression(item);
+                }
+
+                self.emit(Op::MakeList(items.len()));
+            }
+            ExpressionKind::Call(ref callee, ref args) => match callee.kind {
+                ExpressionKind::Variable(name) => self.call(name, args),
+                _ => {
+                    for arg in args {
+                        self.expression(arg);
+                    }
+
+                    self.expression(callee);
+                    self.emit(Op::CallValue(args.len()));
+                }
+            },
+        }
+
+        self.span = outer_span;
+    }
+}
+
+// Function bodies that can reach their scope by name (nested definitions or
+// `meeseeks` capturing it, `{eval}`, scope declarations, microverses,
+// imports) keep their locals in the scope chain instead of in slots.
+fn is_dynamic(statements: &[Statement]) -> bool {
+    statements.iter().any(|statement| match statement.kind {
+        StatementKind::Function(_, _, _)
+        | StatementKind::Global(_)
+        | StatementKind::Nonlocal(_)
+        | StatementKind::DylibLoad(_, _)
+        | StatementKind::Import(_, _) => true,
+        StatementKind::Assignment(_, ref exp)
+        | StatementKind::Print(ref exp)
+        | StatementKind::PrintNoNl(ref exp)
+        | StatementKind::ListAppend(_, ref exp)
+        | StatementKind::Return(ref exp)
+        | StatementKind::Rethrow(ref exp)
+        | StatementKind::Throw(ref exp) => reaches_scope(exp),
+        StatementKind::ListAssign(_, ref indexes, ref exp) => {
+            indexes.iter().any(reaches_scope) || reaches_scope(exp)
+        }
+        StatementKind::ListDelete(_, ref indexes) => indexes.iter().any(reaches_scope),
+        StatementKind::If(ref condition, ref if_body, ref else_body) => {
+            reaches_scope(condition)
+                || is_dynamic(if_body)
+                || else_body.as_ref().is_some_and(|body| is_dynamic(body))
+        }
+        StatementKind::While(ref condition, ref body) => {
+            reaches_scope(condition) || is_dynamic(body)
+        }
+        StatementKind::For(_, ref iterable, ref body) => {
+            let reaches = match *iterable {
+                Iterable::Each(ref exp) => reaches_scope(exp),
+                Iterable::Range(ref start, ref end) => reaches_scope(start) || reaches_scope(end),
+            };
+
+            reaches || is_dynamic(body)
+        }
+        StatementKind::Catch(ref try_block, _, ref catch) => {
+            is_dynamic(try_block) || is_dynamic(catch)
+        }
+        StatementKind::Labeled(_, ref inner) => is_dynamic(std::slice::from_ref(&**inner)),
+        StatementKind::FunctionCall(_, ref args) => args.iter().any(reaches_scope),
+        StatementKind::Call(ref call) => reaches_scope(call),
+        StatementKind::Delete(_)
+        | StatementKind::ListNew(_)
+        | StatementKind::MapNew(_)
+        | StatementKind::Input(_)
+        | StatementKind::Break(_)
+        | StatementKind::Continue(_) => false,
+    })
+}
+
+fn reaches_scope(expression: &Expression) -> bool {
+    match expression.kind {
+        ExpressionKind::Eval(_) | ExpressionKind::Lambda(_, _) => true,
+        ExpressionKind::OpExp(ref left, _, ref right) => {
+            reaches_scope(left) || reaches_scope(right)
+        }
+        ExpressionKind::ListIndex(ref target, ref index) => {
+            reaches_scope(target) || reaches_scope(index)
+        }
+        ExpressionKind::Not(ref exp) => reaches_scope(exp),
+        ExpressionKind::FunctionCall(_, ref exps) | ExpressionKind::List(ref exps) => {
+            exps.iter().any(reaches_scope)
+        }
+        ExpressionKind::Call(ref callee, ref args) => {
+            reaches_scope(callee) || args.iter().any(reaches_scope)
+        }
+        ExpressionKind::Variable(_) | ExpressionKind::Value(_) | ExpressionKind::ListLength(_) => {
+            false
+        }
+    }
+}
+
+fn collect_locals(statements: &[Statement], locals: &mut Vec<Ident>) {
+    for statement in statements {
+        match statement.kind {
+            StatementKind::Assignment(name, _)
+            | StatementKind::ListNew(name)
+            | StatementKind::MapNew(name)
+            | StatementKind::Input(name)
+                if !locals.contains(&name) =>
+            {
+                locals.push(name);
+            }
+            StatementKind::If(_, ref if_body, ref else_body) => {
+                collect_locals(if_body, locals);
+                if let Some(ref else_body) = *else_body {
+                    collect_locals(else_body, locals);
+                }
+            }
+            StatementKind::While(_, ref body) => collect_locals(body, locals),
+            StatementKind::Labeled(_, ref inner) => {
+                collect_locals(std::slice::from_ref(&**inner), locals)
+            }
+            // The loop variable gets its own slot when the loop is compiled.
+            StatementKind::For(_, _, ref body) => collect_locals(body, locals),
+            StatementKind::Catch(ref try_block, name, ref catch) => {
+                collect_locals(try_block, locals);
+                if let Some(name) = name {
+                    if !locals.contains(&name) {
+                        locals.push(name);
+                    }
+                }
+                collect_locals(catch, locals);
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,479 @@
+use crate::{
+    error::{self, EitherError, ErrorKind, ErrorValue, ErrorWithContext, SwResult},
+    expression,
+    span::Span,
+    state::State,
+    statement::Statement,
+    symbols::Ident,
+    value::{self, Closure, Items, Value},
+    Operator,
+};
+
+mod compiler;
+
+#[cfg(test)]
+mod test;
+
+pub use self::compiler::Compiler;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Var {
+    Local(usize),
+    Name(usize),
+}
+
+/// Where a binary operator finds its right operand. Constants and variables
+/// are read in place instead of being copied onto the stack first.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operand {
+    Stack,
+    Constant(usize),
+    Var(Var),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Constant(usize),
+    Load(Var),
+    Store(Var),
+    Delete(Var),
+    Index(Var),
+    IndexValue,
+    Length(Var),
+    Append(Var),
+    AssignIndex(Var, usize),
+    DeleteIndex(Var, usize),
+    Call(Var, usize),
+    CallValue(usize),
+    Input(Var),
+    Binary(Operator, Operand),
+    Not,
+    Eval,
+    Print,
+    PrintNoNl,
+    Jump(usize),
+    JumpIfFalse(usize),
+    Iter,
+    IterRange,
+    Next(usize),
+    PopIter,
+    PushScope(usize),
+    PopScope,
+    PushHandler(usize),
+    PopHandler,
+    MakeClosure(usize),
+    MakeList(usize),
+    Return,
+    Rethrow,
+    Throw,
+    Pop,
+    Exec(usize),
+}
+
+#[derive(Debug)]
+pub struct Chunk {
+    ops: Vec<Op>,
+    places: Vec<usize>,
+    spans: Vec<Span>,
+    statements: Vec<Statement>,
+    constants: Vec<Value>,
+    names: Vec<Ident>,
+    functions: Vec<(Vec<Ident>, Vec<Statement>)>,
+    locals: Vec<Ident>,
+    // The spans of the variables binary operators read in place, by op. Only
+    // needed when the variable turns out not to exist.
+    reads: Vec<(usize, Span)>,
+    slots: bool,
+}
+
+struct Frame<'a> {
+    chunk: &'a Chunk,
+    slots: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    iters: Vec<Items>,
+    handlers: Vec<Handler>,
+}
+
+/// Where to go when an error is raised, and how much of the frame's stacks
+/// to keep when getting there.
+struct Handler {
+    target: usize,
+    stack: usize,
+    iters: usize,
+    scopes: usize,
+}
+
+pub fn run(state: &mut State, statements: &[Statement]) -> Result<(), ErrorWithContext> {
+    let chunk = Compiler::compile(statements);
+    Frame::new(&chunk).execute(state)
+}
+
+pub(crate) fn call(
+    state: &mut State,
+    closure: &Closure,
+    args: Vec<Value>,
+) -> Result<(), ErrorWithContext> {
+    let chunk = closure
+        .chunk
+        .get_or_init(|| Compiler::compile_function(&closure.params, &closure.body));
+
+    if !chunk.slots {
+        for (name, arg) in closure.params.iter().zip(args) {
+            state.insert(*name, arg);
+        }
+
+        return Frame::new(chunk).execute(state);
+    }
+
+    // The parameters come first in the slots.
+    let mut slots: Vec<_> = args.into_iter().map(Some).collect();
+    slots.resize(chunk.locals.len(), None);
+
+    Frame::with_slots(chunk, slots).execute(state)
+}
+
+// Runs closures without going through `State::call_value`, which is left
+// with native functions and the errors for everything else.
+fn call_value(state: &State, name: Ident, function: &Value, args: Vec<Value>) -> SwResult<Value> {
+    match *function {
+        Value::Function(ref closure) if closure.params.len() == args.len() => {
+            let mut child_state = state.call_frame(closure);
+            call(&mut child_state, closure, args)?;
+
+            child_state
+                .take_return()
+                .ok_or_else(|| ErrorKind::NoReturn(name.to_string()).into())
+        }
+        _ => state.call_value(name, function, args),
+    }
+}
+
+impl<'a> Frame<'a> {
+    fn new(chunk: &'a Chunk) -> Self {
+        let slots = if chunk.slots { chunk.locals.len() } else { 0 };
+
+        Self::with_slots(chunk, vec![None; slots])
+    }
+
+    fn with_slots(chunk: &'a Chunk, slots: Vec<Option<Value>>) -> Self {
+        Self {
+            chunk,
+            slots,
+            stack: Vec::new(),
+            iters: Vec::new(),
+            handlers: Vec::new(),
+        }
+    }
+
+    fn execute(&mut self, state: &mut State) -> Result<(), ErrorWithContext> {
+        let mut pc = 0;
+        // Leaving early skips the ends of the for loops being run.
+        let scopes = state.loop_depth();
+
+        while pc < self.chunk.ops.len() {
+            match self.step(state, pc) {
+                Ok(next) => pc = next,
+                Err(e) => {
+                    let place = self.chunk.statements[self.chunk.places[pc]].clone();
+                    let err = match e.at(self.chunk.spans[pc]) {
+                        EitherError::WithContext(e) => e,
+                        EitherError::WithSpan(kind, span) => {
+                            ErrorWithContext::spanned(*kind, place, span)
+                        }
+                        EitherError::NoContext(kind) => ErrorWithContext::new(*kind, place),
+                    };
+
+                    match self.handlers.pop() {
+                        Some(handler) => {
+                            self.stack.truncate(handler.stack);
+                            self.iters.truncate(handler.iters);
+                            state.pop_loop_scopes(handler.scopes);
+                            self.stack
+                                .push(Value::Error(ErrorValue::new(&err, &state.files())));
+                            pc = handler.target;
+                        }
+                        None => {
+                            state.pop_loop_scopes(scopes);
+                            return Err(err);
+                        }
+                    }
+                }
+            }
+        }
+
+        state.pop_loop_scopes(scopes);
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("schwift vm should never pop an empty stack")
+    }
+
+    fn top(&self) -> &Value {
+        self.stack
+            .last()
+            .expect("schwift vm should never read an empty stack")
+    }
+
+    fn top_mut(&mut self) -> &mut Value {
+        self.stack
+            .last_mut()
+            .expect("schwift vm should never read an empty stack")
+    }
+
+    fn store(&mut self, state: &mut State, var: Var) {
+        let value = self.pop();
+        match var {
+            Var::Local(slot) => self.slots[slot] = Some(value),
+            Var::Name(idx) => state.insert(self.chunk.names[idx], value),
+        }
+    }
+
+    fn name(&self, var: Var) -> Ident {
+        match var {
+            Var::Local(slot) => self.chunk.locals[slot],
+            Var::Name(idx) => self.chunk.names[idx],
+        }
+    }
+
+    fn slot(&mut self, var: Var) -> Option<&mut Value> {
+        match var {
+            Var::Local(slot) => self.slots[slot].as_mut(),
+            Var::Name(_) => None,
+        }
+    }
+
+    fn load(&mut self, state: &State, var: Var) -> SwResult<Value> {
+        match self.slot(var) {
+            Some(value) => Ok(value.clone()),
+            None => Ok(state.get(self.name(var))?.clone()),
+        }
+    }
+
+    fn with_value<T, F>(&mut self, state: &State, var: Var, f: F) -> SwResult<T>
+    where
+        F: FnOnce(&Value) -> SwResult<T>,
+    {
+        let name = self.name(var);
+        match self.slot(var) {
+            Some(value) => f(value),
+            None => f(&*state.get(name)?),
+        }
+    }
+
+    fn with_value_mut<T, F>(&mut self, state: &State, var: Var, f: F) -> SwResult<T>
+    where
+        F: FnOnce(&mut Value) -> SwResult<T>,
+    {
+        let name = self.name(var);
+        match self.slot(var) {
+            Some(value) => f(value),
+            None => f(&mut *state.get_mut(name)?),
+        }
+    }
+
+    fn with_list<T, F>(&mut self, state: &State, var: Var, f: F) -> SwResult<T>
+    where
+        F: FnOnce(&mut Vec<Value>) -> SwResult<T>,
+    {
+        let name = self.name(var);
+        match self.slot(var) {
+            Some(Value::List(ref mut list)) => f(list),
+            Some(value) => Err(ErrorKind::IndexUnindexable(value.get_type()).into()),
+            None => f(&mut *state.get_list(name)?),
+        }
+    }
+
+    // Applies `operator` to the top of the stack and `var` without cloning it.
+    fn apply_var(&self, state: &State, operator: Operator, var: Var, pc: usize) -> SwResult<Value> {
+        if let Var::Local(slot) = var {
+            if let Some(ref right) = self.slots[slot] {
+                return operator.apply(self.top(), right);
+            }
+        }
+
+        let right = state
+            .get(self.name(var))
+            .map_err(|e| e.at(self.read_span(pc)))?;
+        operator.apply(self.top(), &right)
+    }
+
+    fn read_span(&self, pc: usize) -> Span {
+        let idx = self
+            .chunk
+            .reads
+            .binary_search_by_key(&pc, |&(at, _)| at)
+            .expect("schwift vm should know where every variable operand was read");
+
+        self.chunk.reads[idx].1
+    }
+
+    /// Runs the op at `pc`, giving back the pc of the op to run next.
+    #[inline(always)]
+    fn step(&mut self, state: &mut State, pc: usize) -> SwResult<usize> {
+        match self.chunk.ops[pc] {
+            Op::Constant(idx) => self.stack.push(self.chunk.constants[idx].clone()),
+            Op::Load(var) => {
+                let value = self.load(state, var)?;
+                self.stack.push(value);
+            }
+            Op::Store(var) => self.store(state, var),
+            Op::Delete(var) => match var {
+                Var::Local(slot) => {
+                    if self.slots[slot].take().is_none() {
+                        return Err(ErrorKind::UnknownVariable(self.name(var).to_string()).into());
+                    }
+                }
+                Var::Name(idx) => state.delete(self.chunk.names[idx])?,
+            },
+            Op::Index(var) => {
+                let index = self.pop();
+                let value = self.with_value(state, var, |list| list.index(&index))?;
+                self.stack.push(value);
+            }
+            Op::IndexValue => {
+                let index = self.pop();
+                let value = self.pop().index(&index)?;
+                self.stack.push(value);
+            }
+            Op::Length(var) => {
+                let value = self.with_value(state, var, expression::list_length)?;
+                self.stack.push(value);
+            }
+            Op::Append(var) => {
+                let value = self.pop();
+                self.with_list(state, var, |list| {
+                    list.push(value);
+                    Ok(())
+                })?;
+            }
+            Op::AssignIndex(var, depth) => {
+                let path = self.stack.split_off(self.stack.len() - depth);
+                let value = self.pop();
+                self.with_value_mut(state, var, |target| target.assign_nested(&path, value))?;
+            }
+            Op::DeleteIndex(var, depth) => {
+                let path = self.stack.split_off(self.stack.len() - depth);
+                self.with_value_mut(state, var, |target| target.delete_nested(&path))?;
+            }
+            Op::Call(var, argc) => {
+                let args = self.stack.split_off(self.stack.len() - argc);
+                let function = self.load(state, var)?;
+                let value = call_value(state, self.name(var), &function, args)?;
+                self.stack.push(value);
+            }
+            Op::CallValue(argc) => {
+                let function = self.pop();
+                let args = self.stack.split_off(self.stack.len() - argc);
+                let value = call_value(state, state.anonymous(), &function, args)?;
+                self.stack.push(value);
+            }
+            Op::Input(var) => {
+                let value = state.read_input()?;
+                self.stack.push(value);
+                self.store(state, var);
+            }
+            Op::Binary(operator, operand) => {
+                let value = match operand {
+                    Operand::Stack => {
+                        let right = self.pop();
+                        operator.apply(self.top(), &right)?
+                    }
+                    Operand::Constant(idx) => {
+                        operator.apply(self.top(), &self.chunk.constants[idx])?
+                    }
+                    Operand::Var(var) => self.apply_var(state, operator, var, pc)?,
+                };
+                *self.top_mut() = value;
+            }
+            Op::Not => {
+                let value = self.pop().not()?;
+                self.stack.push(value);
+            }
+            Op::Eval => {
+                let value = expression::eval_value(&self.pop(), state)?;
+                self.stack.push(value);
+            }
+            Op::Print => {
+                let value = self.pop();
+                value.println(state.io().stdout())?;
+            }
+            Op::PrintNoNl => {
+                let value = self.pop();
+                value.print(state.io().stdout())?;
+            }
+            Op::Jump(target) => return Ok(target),
+            Op::JumpIfFalse(target) => match self.pop() {
+                Value::Bool(true) => {}
+                Value::Bool(false) => return Ok(target),
+                value => {
+                    return Err(ErrorKind::UnexpectedType {
+                        expected: value::Type::Bool,
+                        actual: value.get_type(),
+                    }
+                    .into())
+                }
+            },
+            Op::Iter => {
+                let items = self.pop().items()?;
+                self.iters.push(items);
+            }
+            Op::IterRange => {
+                let end = self.pop();
+                let start = self.pop();
+                self.iters.push(Items::range(&start, &end)?);
+            }
+            Op::Next(target) => {
+                let items = self
+                    .iters
+                    .last_mut()
+                    .expect("schwift vm should only loop inside a for loop");
+
+                match items.next() {
+                    Some(item) => self.stack.push(item),
+                    None => {
+                        self.iters.pop();
+                        return Ok(target);
+                    }
+                }
+            }
+            Op::PopIter => {
+                self.iters.pop();
+            }
+            Op::PushScope(idx) => state.push_loop_scope(self.chunk.names[idx]),
+            Op::PopScope => state.pop_loop_scopes(state.loop_depth() - 1),
+            Op::PushHandler(target) => self.handlers.push(Handler {
+                target,
+                stack: self.stack.len(),
+                iters: self.iters.len(),
+                scopes: state.loop_depth(),
+            }),
+            Op::PopHandler => {
+                self.handlers.pop();
+            }
+            Op::MakeClosure(idx) => {
+                let (ref params, ref body) = self.chunk.functions[idx];
+                self.stack.push(state.closure(params, body));
+            }
+            Op::MakeList(len) => {
+                let items = self.stack.split_off(self.stack.len() - len);
+                self.stack.push(Value::List(items));
+            }
+            Op::Return => {
+                let value = self.pop();
+                state.set_return(value);
+                return Ok(self.chunk.ops.len());
+            }
+            Op::Rethrow => return Err(error::rethrow(self.pop())),
+            Op::Throw => return Err(ErrorKind::Thrown(self.pop()).into()),
+            Op::Pop => {
+                self.pop();
+            }
+            Op::Exec(idx) => state.execute(&self.chunk.statements[idx])?,
+        }
+
+        Ok(pc + 1)
+    }
+}
@@ -0,0 +1,590 @@
+use crate::{
+    error::{ErrorKind as EKind, ErrorWithContext},
+    grammar,
+    io::{SharedBuffer, Streams},
+    state::State,
+    symbols::Ident,
+    value::Value,
+    vm::{Compiler, Op, Operand, Var},
+    Operator,
+};
+
+fn run_with(
+    code: &str,
+    input: &'static str,
+    use_vm: bool,
+) -> (State, String, Result<(), ErrorWithContext>) {
+    let stdout = SharedBuffer::new();
+    let mut state = State::with_io(Streams::new(input.as_bytes(), stdout.clone(), Vec::new()));
+    state.use_vm(use_vm);
+
+    let statements = grammar::file(code).unwrap();
+    let result = state.run(&statements);
+
+    (state, stdout.contents(), result)
+}
+
+fn assert_parity(code: &str, input: &'static str) -> (State, String) {
+    let (_, walker_out, walker_result) = run_with(code, input, false);
+    let (vm_state, vm_out, vm_result) = run_with(code, input, true);
+
+    assert_eq!(walker_out, vm_out);
+    assert_eq!(walker_result, vm_result);
+
+    (vm_state, vm_out)
+}
+
+#[test]
+fn test_vm_is_prime() {
+    let (state, _) = assert_parity(
+        r#"
+    isPrime(x) :<
+        i squanch 2
+        while (i less x) :<
+            if ((x % i) == 0) :<
+                return morty
+            >:
+            i squanch (i + 1)
+        >:
+        return rick
+    >:
+
+    big squanch isPrime(524287)
+    small squanch isPrime(12)
+    "#,
+        "",
+    );
+
+    assert_eq!(*state.get("big").unwrap(), Value::new(true));
+    assert_eq!(*state.get("small").unwrap(), Value::new(false));
+}
+
+#[test]
+fn test_vm_lists_and_printing() {
+    let (_, out) = assert_parity(
+        r#"
+    nums on a cob
+    i squanch 0
+    while (i less 5) :<
+        nums assimilate (i * i)
+        i squanch (i + 1)
+    >:
+    nums[0] squanch "zero"
+    squanch nums[1]
+    show me what you got! nums[2]
+    show me what you got nums
+    show me what you got (nums squanch)
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "9[\"zero\", 4, 9, 16]\n4\n");
+}
+
+#[test]
+fn test_vm_closures_and_input() {
+    let (state, _) = assert_parity(
+        r#"
+    makeCounter(start) :<
+        count squanch start
+        counter() :<
+            nonlocal count
+            count squanch (count + 1)
+            return count
+        >:
+        return counter
+    >:
+
+    portal gun start
+    c squanch makeCounter(10)
+    c()
+    x squanch c()
+    y squanch { (("(" + start) + " + 1)") }
+    "#,
+        "5\n",
+    );
+
+    assert_eq!(*state.get("x").unwrap(), Value::new(12));
+    assert_eq!(*state.get("y").unwrap(), Value::new(6));
+}
+
+#[test]
+fn test_vm_unset_local_falls_back_to_outer_scope() {
+    let (state, _) = assert_parity(
+        r#"
+    x squanch 5
+    f(set) :<
+        if set :<
+            x squanch 1
+        >:
+        return x
+    >:
+
+    a squanch f(rick)
+    b squanch f(morty)
+    "#,
+        "",
+    );
+
+    assert_eq!(*state.get("a").unwrap(), Value::new(1));
+    assert_eq!(*state.get("b").unwrap(), Value::new(5));
+}
+
+#[test]
+fn test_vm_catch() {
+    let (_, out) = assert_parity(
+        r#"
+    risky(x) :<
+        l on a cob
+        return l[x]
+    >:
+
+    normal plan :<
+        show me what you got "before"
+        y squanch risky(3)
+        show me what you got "unreachable"
+    >: plan for failure :<
+        show me what you got "caught"
+    >:
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "before\ncaught\n");
+}
+
+#[test]
+fn test_vm_errors_match_tree_walker() {
+    let programs = [
+        "x squanch (1 + nope)",
+        "while 1 :<\n x squanch 1\n>:",
+        "if \"yes\" :<\n x squanch 1\n>:",
+        "l on a cob\nl[2] squanch 1",
+        "l on a cob\nsquanch l[\"a\"]",
+        "f(a) :<\n return a\n>:\nf(1, 2)",
+        "f(a) :<\n x squanch a\n>:\nf(1)",
+        "f(a) :<\n return (a[0] + 1)\n>:\nf(3)",
+        "f(a) :<\n y squanch (a + z)\n z squanch 1\n return y\n>:\nf(1)",
+        "x squanch 1\nx assimilate 2",
+        "throw 42",
+        "squanch nope",
+    ];
+
+    for program in &programs {
+        let (_, _, result) = run_with(program, "", true);
+        assert!(result.is_err(), "{} should fail", program);
+        assert_parity(program, "");
+    }
+
+    let (_, _, result) = run_with("f(a) :<\n x squanch a\n>:\nf(1)", "", true);
+    assert_eq!(*result.unwrap_err().kind(), EKind::NoReturn("f".into()));
+}
+
+#[test]
+fn test_simple_functions_use_slots() {
+    let body = grammar::file("i squanch 0\nreturn (i + x)").unwrap();
+    let chunk = Compiler::compile_function(&[Ident::intern("x")], &body);
+
+    assert!(chunk.slots);
+    assert_eq!(chunk.locals, vec![Ident::intern("x"), Ident::intern("i")]);
+}
+
+#[test]
+fn test_binary_operands_are_read_in_place() {
+    let body = grammar::file("i squanch 0\nreturn ((i + x) * 2)").unwrap();
+    let chunk = Compiler::compile_function(&[Ident::intern("x")], &body);
+
+    assert_eq!(
+        chunk.ops[2..],
+        [
+            Op::Load(Var::Local(1)),
+            Op::Binary(Operator::Add, Operand::Var(Var::Local(0))),
+            Op::Binary(Operator::Multiply, Operand::Constant(1)),
+            Op::Return,
+        ]
+    );
+}
+
+#[test]
+fn test_loop_variables_get_their_own_slots() {
+    let body = grammar::file(
+        "i squanch 10\nfor i in [1] :<\n for i in [2] :<\n >:\n>:\nfor j in [3] :<\n>:\nreturn i",
+    )
+    .unwrap();
+    let chunk = Compiler::compile_function(&[], &body);
+
+    assert!(chunk.slots);
+    assert_eq!(
+        chunk.locals,
+        vec![
+            Ident::intern("i"),
+            Ident::intern("i"),
+            Ident::intern("i"),
+            Ident::intern("j")
+        ]
+    );
+}
+
+#[test]
+fn test_dynamic_functions_use_names() {
+    let body = grammar::file("inner() :<\n return 1\n>:\nreturn inner()").unwrap();
+    let chunk = Compiler::compile_function(&[], &body);
+    assert!(!chunk.slots);
+
+    let body = grammar::file("return { \"x\" }").unwrap();
+    let chunk = Compiler::compile_function(&[Ident::intern("x")], &body);
+    assert!(!chunk.slots);
+}
+
+#[test]
+fn test_vm_catch_binding_and_rethrow() {
+    let (state, out) = assert_parity(
+        r#"
+    risky(x) :<
+        normal plan :<
+            return (x + 1)
+        >: plan for failure e :<
+            show me what you got e["kind"]
+            rethrow e
+        >:
+    >:
+
+    normal plan :<
+        y squanch risky("a")
+    >: plan for failure caught :<
+        line squanch caught["line"]
+    >:
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "InvalidBinaryExpression\n");
+    assert_eq!(*state.get("line").unwrap(), Value::new(4));
+
+    assert_parity(
+        "normal plan :<\n x squanch nope\n>: plan for failure e :<\n rethrow e\n>:",
+        "",
+    );
+}
+
+#[test]
+fn test_vm_maps() {
+    let (_, out) = assert_parity(
+        r#"
+    count(words) :<
+        counts on a plumbus
+        i squanch 0
+        while (i less (words squanch)) :<
+            word squanch words[i]
+            normal plan :<
+                counts[word] squanch (counts[word] + 1)
+            >: plan for failure :<
+                counts[word] squanch 1
+            >:
+            i squanch (i + 1)
+        >:
+        return counts
+    >:
+
+    words on a cob
+    words assimilate "wubba"
+    words assimilate "lubba"
+    words assimilate "wubba"
+    show me what you got count(words)
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "{\"lubba\": 1, \"wubba\": 2}\n");
+}
+
+#[test]
+fn test_vm_higher_order_functions() {
+    let (_, out) = assert_parity(
+        r#"
+    makeAdder(n) :<
+        add(x) :<
+            return (x + n)
+        >:
+        return add
+    >:
+
+    square(x) :<
+        return (x * x)
+    >:
+
+    plus(a, b) :<
+        return (a + b)
+    >:
+
+    l on a cob
+    l assimilate 1
+    l assimilate 2
+    l assimilate 3
+
+    adders on a cob
+    adders assimilate makeAdder(100)
+
+    show me what you got makeAdder(1)(2)
+    show me what you got adders[0](1)
+    show me what you got map(l, square)
+    show me what you got reduce(map(l, makeAdder(1)), plus, 0)
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "3\n101\n[1, 4, 9]\n9\n");
+}
+
+#[test]
+fn test_vm_lambdas() {
+    let (_, out) = assert_parity(
+        r#"
+    makeScaler(factor) :<
+        return meeseeks (x) -> (x * factor)
+    >:
+
+    l on a cob
+    l assimilate 1
+    l assimilate 2
+
+    show me what you got map(l, makeScaler(3))
+    minus squanch meeseeks (a, b) :<
+        return (a - b)
+    >:
+    show me what you got minus(5, 2)
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "[3, 6]\n3\n");
+}
+
+#[test]
+fn test_vm_nested_indexing() {
+    let (_, out) = assert_parity(
+        r#"
+    identity(n) :<
+        m on a cob
+        i squanch 0
+        while i less n :<
+            row on a cob
+            j squanch 0
+            while j less n :<
+                row assimilate 0
+                j squanch j + 1
+            >:
+            m assimilate row
+            m[i][i] squanch 1
+            i squanch i + 1
+        >:
+        squanch m[0][1]
+        return m
+    >:
+
+    show me what you got identity(3)
+    show me what you got identity(2)[1][1]
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "[[1, 0], [0, 1, 0], [0, 0, 1]]\n1\n");
+}
+
+#[test]
+fn test_vm_list_literals() {
+    let (_, out) = assert_parity(
+        r#"
+    squares(n) :<
+        l squanch []
+        i squanch 1
+        while i lesssquanch n :<
+            l squanch concat(l, [i * i])
+            i squanch i + 1
+        >:
+        return l
+    >:
+
+    show me what you got [squares(3), [rick, "morty"], []]
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "[[1, 4, 9], [rick, \"morty\"], []]\n");
+}
+
+#[test]
+fn test_vm_loop_variables_are_scoped() {
+    let (state, out) = assert_parity(
+        r#"
+    shadowed() :<
+        i squanch 10
+        total squanch 0
+        for i in 0 up to 3 :<
+            total squanch total + i
+            i squanch 100
+        >:
+        return [i, total]
+    >:
+
+    counters() :<
+        fs on a cob
+        for i in 0 up to 2 :<
+            fs assimilate meeseeks () -> i
+        >:
+        return fs[0]()
+    >:
+
+    show me what you got shadowed()
+    show me what you got counters()
+
+    x squanch "outside"
+    normal plan :<
+        for x in [1, 2] :<
+            throw x
+        >:
+    >: plan for failure :<
+    >:
+    outer: for x in [3, 4] :<
+        for y in [5] :<
+            break outer
+        >:
+    >:
+    show me what you got x
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "[10, 3]\n1\noutside\n");
+    assert!(state.get("y").is_err());
+
+    let leaked = "f() :<\n for i in [1] :<\n >:\n return i\n>:\nx squanch f()";
+    let (_, _, result) = run_with(leaked, "", true);
+    assert_eq!(
+        *result.unwrap_err().kind(),
+        EKind::UnknownVariable("i".into())
+    );
+    assert_parity(leaked, "");
+}
+
+#[test]
+fn test_vm_for_loops() {
+    let (_, out) = assert_parity(
+        r#"
+    pairs(n) :<
+        found on a cob
+        for i in 0 up to n :<
+            for c in "ab" :<
+                found assimilate str(i) + c
+            >:
+        >:
+        return found
+    >:
+
+    find(grid, target) :<
+        for row in grid :<
+            for x in row :<
+                if x == target :<
+                    return rick
+                >:
+            >:
+        >:
+        return morty
+    >:
+
+    show me what you got pairs(2)
+    show me what you got find([[1, 2], [3, 4]], 3)
+
+    normal plan :<
+        for row in [[1, 2], [3, 4]] :<
+            for x in row :<
+                if x == 2 :<
+                    throw x
+                >:
+            >:
+        >:
+    >: plan for failure e :<
+        show me what you got e["value"]
+    >:
+
+    for x in [5, 6] :<
+        normal plan :<
+            for y in [x, "oops"] :<
+                show me what you got! y + 1
+            >:
+        >: plan for failure :<
+            show me what you got " caught"
+        >:
+    >:
+    "#,
+        "",
+    );
+
+    assert_eq!(
+        out,
+        "[\"0a\", \"0b\", \"1a\", \"1b\"]\nrick\n2\n6 caught\n7 caught\n"
+    );
+}
+
+#[test]
+fn test_vm_break_continue() {
+    let (_, out) = assert_parity(
+        r#"
+    first_pair(grid, target) :<
+        found squanch -1
+        rows: for row in grid :<
+            for x in row :<
+                if x == target :<
+                    found squanch row
+                    break rows
+                >:
+            >:
+        >:
+        return found
+    >:
+
+    odds(n) :<
+        found on a cob
+        i squanch 0
+        while i less n :<
+            i squanch i + 1
+            if i % 2 == 0 :<
+                continue
+            >:
+            found assimilate i
+        >:
+        return found
+    >:
+
+    show me what you got first_pair([[1, 2], [3, 4], [5, 6]], 4)
+    show me what you got odds(7)
+
+    x squanch "outside"
+    outer: for x in [1, 2, 3] :<
+        normal plan :<
+            for y in "ab" :<
+                if x == 2 :<
+                    continue outer
+                >:
+                if x == 3 :<
+                    break outer
+                >:
+                show me what you got! str(x) + y + " "
+            >:
+        >: plan for failure :<
+            show me what you got "never"
+        >:
+    >:
+    show me what you got x
+
+    normal plan :<
+        throw "after"
+    >: plan for failure e :<
+        show me what you got e["value"]
+    >:
+    "#,
+        "",
+    );
+
+    assert_eq!(out, "[3, 4]\n[1, 3, 5, 7]\n1a 1b outside\nafter\n");
+}
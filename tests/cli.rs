@@ -27,3 +27,90 @@ fn test_brainfuck() {
         .contains("Hello World!")
         .unwrap();
 }
+
+#[test]
+fn test_brainfuck_vm() {
+    let mut f = File::open("examples/hello.brainfuck").unwrap();
+    let mut s = String::new();
+
+    f.read_to_string(&mut s).unwrap();
+
+    assert_cli::Assert::main_binary()
+        .with_args(&["--vm", "examples/brainfuck.y"])
+        .stdin(s.as_bytes())
+        .stdout()
+        .contains("Hello World!")
+        .unwrap();
+}
+
+#[test]
+fn test_repl_prints_expressions() {
+    assert_cli::Assert::main_binary()
+        .stdin("x squanch 10\n(x + 5)\n")
+        .stdout()
+        .contains("15")
+        .unwrap();
+}
+
+#[test]
+fn test_repl_multi_line_blocks() {
+    assert_cli::Assert::main_binary()
+        .with_args(&["repl"])
+        .stdin("double(x) :<\n    return (x * 2)\n>:\ndouble(21)\n")
+        .stdout()
+        .contains("42")
+        .unwrap();
+}
+
+#[test]
+fn test_repl_calls_functions_without_returns() {
+    assert_cli::Assert::main_binary()
+        .stdin("greet(x) :<\n    show me what you got x\n>:\ngreet(\"hi\")\n")
+        .stdout()
+        .contains("hi")
+        .and()
+        .stderr()
+        .is("")
+        .unwrap();
+}
+
+#[test]
+fn test_repl_survives_errors() {
+    assert_cli::Assert::main_binary()
+        .stdin("show me what you got nope\nshow me what you got \"still here\"\n")
+        .stderr()
+        .contains("There's no nope in this universe, Morty!")
+        .and()
+        .stdout()
+        .contains("still here")
+        .unwrap();
+}
+
+#[test]
+fn test_repl_errors_point_at_earlier_inputs() {
+    assert_cli::Assert::main_binary()
+        .stdin("half(x) :<\n    return (x / y)\n>:\nhalf(4)\n")
+        .stderr()
+        .contains("<repl:1>:2:17")
+        .and()
+        .stderr()
+        .contains("2 |     return (x / y)")
+        .unwrap();
+}
+
+#[test]
+fn test_file_errors_go_to_stderr() {
+    let path = std::env::temp_dir().join("schwift_test_file_errors_go_to_stderr.y");
+    std::fs::write(&path, "show me what you got nope\n").unwrap();
+
+    assert_cli::Assert::main_binary()
+        .with_args(&[path.to_str().unwrap()])
+        .fails()
+        .and()
+        .stderr()
+        .contains("There's no nope in this universe, Morty!")
+        .and()
+        .stdout()
+        .is("")
+        .unwrap();
+}
@@ -0,0 +1,330 @@
+use schwift::{
+    error::{EitherError, ErrorKind, RunError},
+    io::{SharedBuffer, Streams},
+    value::Value,
+    Interpreter,
+};
+
+#[test]
+fn test_run_str_keeps_state() {
+    let mut interpreter = Interpreter::new();
+
+    interpreter.run_str("x squanch 10", "first.y").unwrap();
+    interpreter
+        .run_str("y squanch (x * 2)", "second.y")
+        .unwrap();
+
+    assert_eq!(*interpreter.state().get("y").unwrap(), Value::new(20));
+}
+
+#[test]
+fn test_top_level_return_only_ends_its_own_run() {
+    for &use_vm in &[false, true] {
+        let mut interpreter = Interpreter::new();
+        interpreter.use_vm(use_vm);
+
+        interpreter
+            .run_str("x squanch 1\nreturn x\nx squanch 2", "first.y")
+            .unwrap();
+        interpreter
+            .run_str("y squanch 3\nz squanch (x + y)", "second.y")
+            .unwrap();
+
+        assert_eq!(*interpreter.state().get("z").unwrap(), Value::new(4));
+    }
+}
+
+#[test]
+fn test_builtins_are_loaded() {
+    let mut interpreter = Interpreter::new();
+
+    assert_eq!(
+        interpreter.eval_str("ascii(65)", "eval.y").unwrap(),
+        Value::new("A")
+    );
+}
+
+#[test]
+fn test_syntax_error_has_location() {
+    let mut interpreter = Interpreter::new();
+
+    match interpreter.run_str("x squanch 10\ny squanch )", "broken.y") {
+        Err(RunError::Syntax {
+            file, line, column, ..
+        }) => {
+            assert_eq!(file, "broken.y");
+            assert_eq!(line, 2);
+            assert_eq!(column, 11);
+        }
+        other => panic!("expected a syntax error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_runtime_error_points_at_expression() {
+    let mut interpreter = Interpreter::new();
+
+    match interpreter.run_str("x squanch 10\ny squanch (x + z)", "runtime.y") {
+        Err(RunError::Runtime {
+            file,
+            line,
+            column,
+            code_frame,
+            error,
+        }) => {
+            assert_eq!(file, "runtime.y");
+            assert_eq!((line, column), (2, 16));
+            assert_eq!(code_frame, "2 | y squanch (x + z)\n  |                ^");
+            assert_eq!(*error.kind(), ErrorKind::UnknownVariable("z".into()));
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_runtime_error_spans_unparenthesised_operands() {
+    let mut interpreter = Interpreter::new();
+
+    match interpreter.run_str("x squanch 1 + 2 * \"two\" - 3", "infix.y") {
+        Err(RunError::Runtime {
+            column, code_frame, ..
+        }) => {
+            assert_eq!(column, 15);
+            assert_eq!(
+                code_frame,
+                "1 | x squanch 1 + 2 * \"two\" - 3\n  |               ^^^^^^^^^"
+            );
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_runtime_error_inside_function() {
+    let mut interpreter = Interpreter::new();
+    let source = "f(l) :<\n    return (l[0] + 1)\n>:\n\nl on a cob\nx squanch f(l)";
+
+    match interpreter.run_str(source, "function.y") {
+        Err(RunError::Runtime {
+            line,
+            column,
+            code_frame,
+            error,
+            ..
+        }) => {
+            assert_eq!((line, column), (2, 13));
+            assert_eq!(
+                code_frame,
+                "2 |     return (l[0] + 1)\n  |             ^^^^"
+            );
+            assert_eq!(
+                *error.kind(),
+                ErrorKind::IndexOutOfBounds { len: 0, index: 0 }
+            );
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_uncaught_throw_is_reported() {
+    let mut interpreter = Interpreter::new();
+
+    let error = interpreter
+        .run_str("x squanch 1\nthrow \"portal fluid\"", "throw.y")
+        .unwrap_err();
+    let report = error.report();
+
+    assert!(report.starts_with("throw.y:2:1\n"));
+    assert!(report.contains("Your program threw portal fluid and nobody caught it."));
+    assert!(report.contains("2 | throw \"portal fluid\"\n      | ^^^^^^^^^^^^^^^^^^^^"));
+
+    let error = interpreter
+        .run_str(
+            "normal plan :<\n    throw 137\n>: plan for failure e :<\n    rethrow e\n>:",
+            "rethrow.y",
+        )
+        .unwrap_err();
+
+    assert!(error
+        .report()
+        .contains("Your program threw 137 and nobody caught it."));
+}
+
+#[test]
+fn test_run_file_reports_missing_file() {
+    let mut interpreter = Interpreter::new();
+
+    match interpreter.run_file("examples/does_not_exist.y") {
+        Err(RunError::Io { file, .. }) => assert_eq!(file, "examples/does_not_exist.y"),
+        other => panic!("expected an io error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_hello_world_with_captured_io() {
+    let stdout = SharedBuffer::new();
+    let stderr = SharedBuffer::new();
+    let mut interpreter =
+        Interpreter::with_io(Streams::new(&b"Nate\n"[..], stdout.clone(), stderr.clone()));
+
+    interpreter.run_file("examples/hello.y").unwrap();
+
+    assert_eq!(stdout.contents(), "Enter your name: \nHello, Nate\n");
+    assert_eq!(stderr.contents(), "");
+}
+
+fn module_dir(name: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("schwift-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    for (file, source) in files {
+        let path = dir.join(file);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+
+    dir
+}
+
+fn runtime_error(result: Result<(), RunError>) -> Box<EitherError> {
+    match result {
+        Err(RunError::Runtime { error, .. }) => error,
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_imports() {
+    let dir = module_dir(
+        "imports",
+        &[
+            (
+                "main.y",
+                "interdimensional cable \"lib/shapes.y\"\n\
+                 interdimensional cable \"lib/shapes.y\" as s\n\
+                 interdimensional cable \"lib/shapes.y\" showing square, sides\n\
+                 show me what you got shapes[\"square\"](3)\n\
+                 show me what you got s[\"sides\"]\n\
+                 show me what you got square(sides)\n",
+            ),
+            (
+                "lib/shapes.y",
+                "interdimensional cable \"helpers.y\" showing times\n\
+                 show me what you got \"loading shapes\"\n\
+                 sides squanch 4\n\
+                 square(x) :<\n    return times(x, x)\n>:\n",
+            ),
+            ("lib/helpers.y", "times(a, b) :<\n    return (a * b)\n>:\n"),
+        ],
+    );
+
+    for &vm in &[false, true] {
+        let stdout = SharedBuffer::new();
+        let mut interpreter =
+            Interpreter::with_io(Streams::new(&b""[..], stdout.clone(), SharedBuffer::new()));
+        interpreter.use_vm(vm);
+
+        interpreter.run_file(dir.join("main.y")).unwrap();
+
+        assert_eq!(stdout.contents(), "loading shapes\n9\n4\n16\n");
+        assert!(interpreter.state().get("times").is_err());
+    }
+}
+
+#[test]
+fn test_import_search_path() {
+    let dir = module_dir(
+        "search-path",
+        &[("vendor/portal.y", "fluid squanch \"green\"\n")],
+    );
+    let mut interpreter = Interpreter::new();
+
+    let error = runtime_error(interpreter.run_str("interdimensional cable \"portal.y\"", "main.y"));
+    assert_eq!(*error.kind(), ErrorKind::ModuleNotFound("portal.y".into()));
+
+    interpreter.add_search_path(dir.join("vendor"));
+    interpreter
+        .run_str(
+            "interdimensional cable \"portal.y\" showing fluid",
+            "main.y",
+        )
+        .unwrap();
+
+    assert_eq!(
+        *interpreter.state().get("fluid").unwrap(),
+        Value::new("green")
+    );
+}
+
+#[test]
+fn test_errors_in_modules_show_module_code() {
+    let dir = module_dir(
+        "module-frames",
+        &[("m.y", "area(x) :<\n    return x * y\n>:\n")],
+    );
+    let main = dir.join("main.y").display().to_string();
+    let mut interpreter = Interpreter::new();
+
+    match interpreter.run_str(
+        "interdimensional cable \"m.y\" showing area\nshow me what you got area(2)",
+        &main,
+    ) {
+        Err(RunError::Runtime {
+            file,
+            line,
+            column,
+            code_frame,
+            ..
+        }) => {
+            assert_eq!(file, dir.join("m.y").display().to_string());
+            assert_eq!((line, column), (2, 16));
+            assert_eq!(code_frame, "2 |     return x * y\n  |                ^");
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_import_errors() {
+    let dir = module_dir(
+        "import-errors",
+        &[
+            ("rick.y", "interdimensional cable \"morty.y\"\n"),
+            ("morty.y", "interdimensional cable \"rick.y\"\n"),
+            ("lonely.y", "x squanch 1\n"),
+        ],
+    );
+    let mut interpreter = Interpreter::new();
+
+    let main = dir.join("main.y").display().to_string();
+    match interpreter.run_str("interdimensional cable \"rick.y\"", &main) {
+        Err(RunError::Runtime {
+            file, code_frame, ..
+        }) => {
+            assert_eq!(file, dir.join("morty.y").display().to_string());
+            assert_eq!(
+                code_frame,
+                "1 | interdimensional cable \"rick.y\"\n  | ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^"
+            );
+        }
+        other => panic!("expected a runtime error, got {:?}", other),
+    }
+
+    let mut interpreter = Interpreter::new();
+    let error = runtime_error(interpreter.run_file(dir.join("rick.y")));
+    let cycle = format!(
+        "{} -> {} -> {}",
+        dir.join("morty.y").display(),
+        dir.join("rick.y").display(),
+        dir.join("morty.y").display()
+    );
+    assert_eq!(*error.kind(), ErrorKind::ImportCycle(cycle));
+
+    let error =
+        runtime_error(interpreter.run_str("interdimensional cable \"lonely.y\" showing y", &main));
+    assert_eq!(
+        *error.kind(),
+        ErrorKind::MissingExport("lonely.y".into(), "y".into())
+    );
+}